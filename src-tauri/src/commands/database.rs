@@ -0,0 +1,1018 @@
+use crate::db::hash::stable_hash;
+use crate::error::{AppError, Result};
+use crate::models::{
+    MergeReport, MergeStrategy, Pragmas, ReferenceAudit, SyncState, TableMergeCounts,
+    TableSyncState,
+};
+use crate::AppState;
+use rusqlite::types::ValueRef;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use tauri::State;
+use uuid::Uuid;
+
+/// Columns copied for each table when merging databases, keyed by the first column
+/// `idempotency_key` is left out on purpose: it only guards retries on one device.
+const MERGE_TABLES: [(&str, &[&str]); 4] = [
+    (
+        "projects",
+        &["id", "name", "created_at", "updated_at", "deleted_at"],
+    ),
+    (
+        "trees",
+        &[
+            "id",
+            "project_id",
+            "name",
+            "system_prompt",
+            "created_at",
+            "updated_at",
+            "deleted_at",
+            "default_model",
+        ],
+    ),
+    (
+        "nodes",
+        &[
+            "id",
+            "tree_id",
+            "parent_id",
+            "user_content",
+            "assistant_content",
+            "summary",
+            "model",
+            "tokens",
+            "created_at",
+            "updated_at",
+            "deleted_at",
+            "failed",
+            "delete_reason_id",
+            "params",
+            "content_hash",
+        ],
+    ),
+    (
+        "settings",
+        &["key", "value", "created_at", "updated_at", "deleted_at"],
+    ),
+];
+
+/// Columns holding project, tree or node IDs, which follow any IDs reassigned by a merge
+const MERGE_ID_COLUMNS: [&str; 4] = ["id", "project_id", "tree_id", "parent_id"];
+
+/// Scan the whole database for references that don't resolve
+/// Foreign keys normally prevent these, but rows written with enforcement off
+/// (external edits, old builds) can still dangle
+#[tauri::command]
+pub fn audit_references(state: State<Arc<AppState>>) -> Result<ReferenceAudit> {
+    let conn = state.db.conn();
+
+    let nodes_with_missing_parent = query_ids(
+        &conn,
+        "SELECT n.id FROM nodes n
+         WHERE n.parent_id IS NOT NULL
+           AND NOT EXISTS (SELECT 1 FROM nodes p WHERE p.id = n.parent_id)
+         ORDER BY n.id ASC",
+    )?;
+
+    let nodes_with_missing_tree = query_ids(
+        &conn,
+        "SELECT n.id FROM nodes n
+         WHERE NOT EXISTS (SELECT 1 FROM trees t WHERE t.id = n.tree_id)
+         ORDER BY n.id ASC",
+    )?;
+
+    let trees_with_missing_project = query_ids(
+        &conn,
+        "SELECT t.id FROM trees t
+         WHERE t.project_id IS NOT NULL
+           AND NOT EXISTS (SELECT 1 FROM projects p WHERE p.id = t.project_id)
+         ORDER BY t.id ASC",
+    )?;
+
+    Ok(ReferenceAudit {
+        nodes_with_missing_parent,
+        nodes_with_missing_tree,
+        trees_with_missing_project,
+    })
+}
+
+/// Move active nodes whose tree no longer exists into a salvage tree
+/// Returns the number of nodes moved
+#[tauri::command]
+pub fn rehome_orphaned_nodes(state: State<Arc<AppState>>, into_tree_id: String) -> Result<usize> {
+    let conn = state.db.conn();
+
+    let target_is_live: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM trees WHERE id = ?1 AND deleted_at IS NULL)",
+        [&into_tree_id],
+        |row| row.get(0),
+    )?;
+    if !target_is_live {
+        return Err(AppError::NotFound(format!("Tree {into_tree_id} not found")));
+    }
+
+    let rehomed = conn.execute(
+        "UPDATE nodes SET tree_id = ?1, updated_at = datetime('now')
+         WHERE deleted_at IS NULL
+           AND NOT EXISTS (SELECT 1 FROM trees t WHERE t.id = nodes.tree_id)",
+        [&into_tree_id],
+    )?;
+
+    Ok(rehomed)
+}
+
+/// Report the connection's current pragma settings
+#[tauri::command]
+pub fn get_pragmas(state: State<Arc<AppState>>) -> Result<Pragmas> {
+    let conn = state.db.conn();
+
+    let synchronous: i64 = conn.pragma_query_value(None, "synchronous", |row| row.get(0))?;
+
+    Ok(Pragmas {
+        journal_mode: conn.pragma_query_value(None, "journal_mode", |row| row.get(0))?,
+        foreign_keys: conn.pragma_query_value(None, "foreign_keys", |row| row.get(0))?,
+        busy_timeout: conn.pragma_query_value(None, "busy_timeout", |row| row.get(0))?,
+        synchronous: match synchronous {
+            0 => "off",
+            1 => "normal",
+            2 => "full",
+            3 => "extra",
+            _ => "unknown",
+        }
+        .to_string(),
+        page_size: conn.pragma_query_value(None, "page_size", |row| row.get(0))?,
+    })
+}
+
+/// Summarize each synced table by row count and latest `updated_at`
+/// Two databases with different fingerprints have diverged. Trashed rows are included,
+/// since trashing a row is itself a change.
+#[tauri::command]
+pub fn get_sync_state(state: State<Arc<AppState>>) -> Result<SyncState> {
+    let conn = state.db.conn();
+
+    let projects = table_sync_state(&conn, "projects")?;
+    let trees = table_sync_state(&conn, "trees")?;
+    let nodes = table_sync_state(&conn, "nodes")?;
+    let settings = table_sync_state(&conn, "settings")?;
+
+    let summary = [&projects, &trees, &nodes, &settings]
+        .iter()
+        .map(|table| {
+            format!(
+                "{}:{}",
+                table.count,
+                table.last_modified.as_deref().unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("|");
+
+    Ok(SyncState {
+        projects,
+        trees,
+        nodes,
+        settings,
+        fingerprint: stable_hash(&summary),
+    })
+}
+
+/// Merge the projects, trees, nodes and settings of another database file into this one
+/// Rows with IDs not present here are inserted. For an ID present in both, the strategy
+/// decides. Settings are keyed by name and always take the most recently modified value.
+/// The other database must be on the same schema version.
+#[tauri::command]
+pub fn merge_database(
+    state: State<Arc<AppState>>,
+    other_path: String,
+    strategy: MergeStrategy,
+) -> Result<MergeReport> {
+    if !Path::new(&other_path).is_file() {
+        return Err(AppError::NotFound(format!(
+            "Database {other_path} not found"
+        )));
+    }
+
+    let mut conn = state.db.conn();
+    conn.execute("ATTACH DATABASE ?1 AS other", [&other_path])?;
+    let report =
+        check_same_schema(&conn, &other_path).and_then(|()| merge_attached(&mut conn, strategy));
+    conn.execute("DETACH DATABASE other", [])?;
+
+    report
+}
+
+/// Write the database schema and contents to a file as SQL statements
+/// Limited to `tables` when given, plus the tables they need to replay. Full-text indexes
+/// are rebuilt from their content table on replay rather than dumped.
+/// Returns the number of bytes written.
+#[tauri::command]
+pub fn export_sql_dump(
+    state: State<Arc<AppState>>,
+    path: String,
+    tables: Option<Vec<String>>,
+) -> Result<u64> {
+    let conn = state.db.conn();
+
+    let mut stmt = conn.prepare(
+        "SELECT type, name, tbl_name, sql
+         FROM sqlite_master
+         WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+         ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'index' THEN 1 ELSE 2 END, rowid",
+    )?;
+    let entries = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    // Shadow tables belong to a virtual table and are recreated along with it
+    let virtual_tables: Vec<&str> = entries
+        .iter()
+        .filter(|(_, _, _, sql)| sql.starts_with("CREATE VIRTUAL TABLE"))
+        .map(|(_, name, _, _)| name.as_str())
+        .collect();
+    let is_shadow = |name: &str| {
+        virtual_tables.iter().any(|vt| {
+            name.len() > vt.len() && name.starts_with(vt) && name[vt.len()..].starts_with('_')
+        })
+    };
+
+    if let Some(ref tables) = tables {
+        for table in tables {
+            let known = entries
+                .iter()
+                .any(|(kind, name, _, _)| kind == "table" && name == table && !is_shadow(name));
+            if !known {
+                return Err(AppError::InvalidInput(format!("Unknown table '{table}'")));
+            }
+        }
+    }
+    // A subset also takes the tables its triggers write into and the content tables its
+    // virtual tables index, otherwise the dump would not replay
+    let subset = tables.as_ref().map(|tables| {
+        let mut subset: HashSet<&str> = tables.iter().map(String::as_str).collect();
+        loop {
+            let before = subset.len();
+            for (kind, name, table, sql) in &entries {
+                let pulls_in = match kind.as_str() {
+                    "trigger" => subset.contains(table.as_str()),
+                    "table" => {
+                        subset.contains(name.as_str()) && virtual_tables.contains(&name.as_str())
+                    }
+                    _ => false,
+                };
+                if !pulls_in {
+                    continue;
+                }
+                for (other_kind, other, _, _) in &entries {
+                    if other_kind == "table"
+                        && other != name
+                        && !is_shadow(other)
+                        && mentions_table(sql, other)
+                    {
+                        subset.insert(other.as_str());
+                    }
+                }
+            }
+            if subset.len() == before {
+                break;
+            }
+        }
+        subset
+    });
+    let selected = |table: &str| {
+        !is_shadow(table) && subset.as_ref().is_none_or(|subset| subset.contains(table))
+    };
+
+    let mut writer = BufWriter::new(File::create(&path)?);
+    let mut bytes = write_line(&mut writer, "PRAGMA foreign_keys=OFF;")?;
+    bytes += write_line(&mut writer, "BEGIN TRANSACTION;")?;
+
+    for (kind, name, table, sql) in &entries {
+        if !selected(table) {
+            continue;
+        }
+        bytes += write_line(&mut writer, &format!("{sql};"))?;
+        if kind == "table" && !virtual_tables.contains(&name.as_str()) {
+            bytes += dump_table_rows(&conn, &mut writer, name)?;
+        }
+    }
+
+    for (_, name, _, sql) in &entries {
+        if virtual_tables.contains(&name.as_str()) && selected(name) && sql.contains("content=") {
+            let name = quote_identifier(name);
+            bytes += write_line(
+                &mut writer,
+                &format!("INSERT INTO {name}({name}) VALUES('rebuild');"),
+            )?;
+        }
+    }
+
+    bytes += write_line(&mut writer, "COMMIT;")?;
+    writer.flush()?;
+
+    Ok(bytes)
+}
+
+/// Helper function to make sure the attached `other` database has the same migrations applied
+fn check_same_schema(conn: &rusqlite::Connection, other_path: &str) -> Result<()> {
+    let tracked: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM other.sqlite_master WHERE type = 'table' AND name = '_migrations')",
+        [],
+        |row| row.get(0),
+    )?;
+    let differing: i64 = if tracked {
+        conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM (SELECT name FROM main._migrations EXCEPT SELECT name FROM other._migrations))
+                  + (SELECT COUNT(*) FROM (SELECT name FROM other._migrations EXCEPT SELECT name FROM main._migrations))",
+            [],
+            |row| row.get(0),
+        )?
+    } else {
+        1
+    };
+
+    if differing > 0 {
+        return Err(AppError::InvalidInput(format!(
+            "Database {other_path} is on a different schema version"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Helper function to merge the attached `other` database in a single transaction
+fn merge_attached(conn: &mut rusqlite::Connection, strategy: MergeStrategy) -> Result<MergeReport> {
+    let tx = conn.transaction()?;
+    // Incoming rows can reference each other in any order; check keys once at commit
+    tx.execute_batch(
+        "PRAGMA defer_foreign_keys = ON;
+         CREATE TEMP TABLE merge_ids (old_id TEXT PRIMARY KEY, new_id TEXT NOT NULL);",
+    )?;
+
+    if strategy == MergeStrategy::KeepBoth {
+        // Incoming rows that collide with a different local row get a fresh ID, and so does
+        // everything under them that collides too, so each copy keeps its whole subtree
+        let changed =
+            "COALESCE(o.updated_at, o.created_at) IS NOT COALESCE(m.updated_at, m.created_at)";
+        let moved = "IN (SELECT old_id FROM temp.merge_ids)";
+        let queries = [
+            format!(
+                "SELECT o.id FROM other.projects o
+                 INNER JOIN main.projects m ON m.id = o.id
+                 WHERE {changed}"
+            ),
+            format!(
+                "SELECT o.id FROM other.trees o
+                 INNER JOIN main.trees m ON m.id = o.id
+                 WHERE {changed} OR o.project_id {moved}"
+            ),
+            format!(
+                "WITH RECURSIVE moved_nodes(id) AS (
+                     SELECT o.id FROM other.nodes o
+                     INNER JOIN main.nodes m ON m.id = o.id
+                     WHERE {changed} OR o.tree_id {moved}
+                     UNION
+                     SELECT o.id FROM other.nodes o
+                     INNER JOIN moved_nodes p ON o.parent_id = p.id
+                 )
+                 SELECT id FROM moved_nodes WHERE id IN (SELECT id FROM main.nodes)"
+            ),
+        ];
+        for query in &queries {
+            for old_id in query_ids(&tx, query)? {
+                tx.execute(
+                    "INSERT INTO temp.merge_ids (old_id, new_id) VALUES (?1, ?2)",
+                    (&old_id, Uuid::new_v4().to_string()),
+                )?;
+            }
+        }
+    }
+
+    let [projects, trees, nodes, settings] =
+        MERGE_TABLES.map(|(table, columns)| merge_table(&tx, table, columns));
+    let report = MergeReport {
+        projects: projects?,
+        trees: trees?,
+        nodes: nodes?,
+        settings: settings?,
+    };
+
+    tx.execute_batch("DROP TABLE temp.merge_ids;")?;
+    tx.commit()?;
+
+    Ok(report)
+}
+
+/// Helper function to merge one table from the attached `other` database
+/// Newer incoming rows overwrite local ones and rows listed in `merge_ids` are inserted
+/// under their new ID; everything else that already exists is skipped.
+fn merge_table(
+    tx: &rusqlite::Transaction<'_>,
+    table: &str,
+    columns: &[&str],
+) -> Result<TableMergeCounts> {
+    let key = columns[0];
+    let values = columns
+        .iter()
+        .map(|column| {
+            if MERGE_ID_COLUMNS.contains(column) {
+                format!(
+                    "COALESCE((SELECT new_id FROM temp.merge_ids WHERE old_id = o.{column}), o.{column})"
+                )
+            } else {
+                format!("o.{column}")
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let updated = tx.execute(
+        &format!(
+            "UPDATE main.{table} AS m
+             SET ({columns}) = (SELECT {values} FROM other.{table} o WHERE o.{key} = m.{key})
+             WHERE EXISTS (
+                 SELECT 1 FROM other.{table} o
+                 WHERE o.{key} = m.{key}
+                   AND COALESCE(o.updated_at, o.created_at) > COALESCE(m.updated_at, m.created_at)
+                   AND o.{key} NOT IN (SELECT old_id FROM temp.merge_ids)
+             )",
+            columns = columns[1..].join(", "),
+            values = values[1..].join(", "),
+        ),
+        [],
+    )?;
+
+    let inserted = tx.execute(
+        &format!(
+            "INSERT INTO main.{table} ({columns})
+             SELECT {values} FROM other.{table} o
+             WHERE o.{key} IN (SELECT old_id FROM temp.merge_ids)
+                OR NOT EXISTS (SELECT 1 FROM main.{table} m WHERE m.{key} = o.{key})",
+            columns = columns.join(", "),
+            values = values.join(", "),
+        ),
+        [],
+    )?;
+
+    let total: usize = tx.query_row(&format!("SELECT COUNT(*) FROM other.{table}"), [], |row| {
+        row.get(0)
+    })?;
+
+    Ok(TableMergeCounts {
+        inserted,
+        updated,
+        skipped: total.saturating_sub(inserted + updated),
+    })
+}
+
+/// Helper function to read a table's row count and latest `updated_at`
+fn table_sync_state(
+    conn: &std::sync::MutexGuard<'_, rusqlite::Connection>,
+    table: &str,
+) -> Result<TableSyncState> {
+    let sync_state = conn.query_row(
+        &format!("SELECT COUNT(*), MAX(updated_at) FROM {table}"),
+        [],
+        |row| {
+            Ok(TableSyncState {
+                count: row.get(0)?,
+                last_modified: row.get(1)?,
+            })
+        },
+    )?;
+
+    Ok(sync_state)
+}
+
+/// Helper function to collect a single column of IDs
+fn query_ids(conn: &rusqlite::Connection, sql: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(sql)?;
+
+    let ids = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(ids)
+}
+
+/// Helper function to write a table's rows as INSERT statements, returning the bytes written
+fn dump_table_rows(
+    conn: &std::sync::MutexGuard<'_, rusqlite::Connection>,
+    writer: &mut impl Write,
+    table: &str,
+) -> Result<u64> {
+    let table = quote_identifier(table);
+    let mut stmt = conn.prepare(&format!("SELECT * FROM {table}"))?;
+    let column_count = stmt.column_count();
+
+    let mut bytes = 0;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let values = (0..column_count)
+            .map(|i| row.get_ref(i).map(sql_literal))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        bytes += write_line(
+            writer,
+            &format!("INSERT INTO {table} VALUES({});", values.join(",")),
+        )?;
+    }
+
+    Ok(bytes)
+}
+
+/// Helper function to check whether SQL text mentions a table as a whole word
+fn mentions_table(sql: &str, table: &str) -> bool {
+    let sql = sql.to_ascii_lowercase();
+    let table = table.to_ascii_lowercase();
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    sql.match_indices(&table).any(|(at, _)| {
+        !sql[..at].ends_with(is_word) && !sql[at + table.len()..].starts_with(is_word)
+    })
+}
+
+/// Digits used to write blob literals
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Helper function to render a value as an SQL literal
+fn sql_literal(value: ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) if f.is_infinite() => {
+            if f > 0.0 { "1e999" } else { "-1e999" }.to_string()
+        }
+        ValueRef::Real(f) => format!("{f:?}"),
+        ValueRef::Text(text) => {
+            format!("'{}'", String::from_utf8_lossy(text).replace('\'', "''"))
+        }
+        ValueRef::Blob(blob) => {
+            let mut hex = String::with_capacity(blob.len() * 2 + 3);
+            hex.push_str("X'");
+            for byte in blob {
+                hex.push(char::from(HEX_DIGITS[usize::from(byte >> 4)]));
+                hex.push(char::from(HEX_DIGITS[usize::from(byte & 0x0f)]));
+            }
+            hex.push('\'');
+            hex
+        }
+    }
+}
+
+/// Helper function to quote an SQL identifier
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Helper function to write one line, returning the bytes written
+fn write_line(writer: &mut impl Write, line: &str) -> Result<u64> {
+    writer.write_all(line.as_bytes())?;
+    writer.write_all(b"\n")?;
+    Ok(line.len() as u64 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::nodes::{create_node, get_node, list_nodes, update_node};
+    use crate::commands::settings::{get_setting, set_setting};
+    use crate::commands::test_support::{new_node, TempPath, TestApp};
+    use crate::commands::trees::{get_tree, update_tree};
+    use crate::models::{CreateNode, GenerationParams};
+    use rusqlite::Connection;
+
+    /// Helper function to run statements with foreign key enforcement off, like an external edit
+    fn write_unchecked(app: &TestApp, sql: &str) {
+        let state = app.state();
+        state
+            .db
+            .conn()
+            .execute_batch(&format!(
+                "PRAGMA foreign_keys = OFF; {sql} PRAGMA foreign_keys = ON;"
+            ))
+            .unwrap();
+    }
+
+    #[test]
+    fn audit_references_reports_dangling_rows() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let node = app.node(&tree.id, None, "turn");
+        write_unchecked(
+            &app,
+            &format!(
+                "UPDATE nodes SET parent_id = 'ghost' WHERE id = '{}';
+                 UPDATE trees SET project_id = 'ghost' WHERE id = '{}';",
+                node.id, tree.id
+            ),
+        );
+
+        let audit = audit_references(app.state()).unwrap();
+
+        assert_eq!(audit.nodes_with_missing_parent, vec![node.id]);
+        assert_eq!(audit.trees_with_missing_project, vec![tree.id]);
+        assert!(audit.nodes_with_missing_tree.is_empty());
+    }
+
+    #[test]
+    fn get_pragmas_reports_connection_settings() {
+        let app = TestApp::new();
+
+        let pragmas = get_pragmas(app.state()).unwrap();
+
+        // `Database::new` only turns on foreign keys; the rest are SQLite's and rusqlite's
+        // defaults, with in-memory test databases always journaling in memory
+        assert_eq!(pragmas.journal_mode, "memory");
+        assert!(pragmas.foreign_keys);
+        assert_eq!(pragmas.busy_timeout, 5000);
+        assert_eq!(pragmas.synchronous, "full");
+        assert_eq!(pragmas.page_size, 4096);
+    }
+
+    #[test]
+    fn rehome_orphaned_nodes_moves_them_to_the_salvage_tree() {
+        let app = TestApp::new();
+        let lost = app.tree(None, "Lost");
+        let salvage = app.tree(None, "Salvage");
+        let root = app.node(&lost.id, None, "root");
+        let child = app.node(&lost.id, Some(&root.id), "child");
+        write_unchecked(
+            &app,
+            &format!("DELETE FROM trees WHERE id = '{}';", lost.id),
+        );
+        assert_eq!(
+            audit_references(app.state())
+                .unwrap()
+                .nodes_with_missing_tree
+                .len(),
+            2
+        );
+
+        assert_eq!(
+            rehome_orphaned_nodes(app.state(), salvage.id.clone()).unwrap(),
+            2
+        );
+
+        assert_eq!(get_node(app.state(), child.id).unwrap().tree_id, salvage.id);
+        assert!(audit_references(app.state())
+            .unwrap()
+            .nodes_with_missing_tree
+            .is_empty());
+        assert!(matches!(
+            rehome_orphaned_nodes(app.state(), "missing".to_string()),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    /// Helper function to replay a dump into a fresh in-memory database
+    fn replay(dump: &str) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(dump).expect("Dump does not replay");
+        conn
+    }
+
+    #[test]
+    fn sql_dump_replays_into_a_fresh_database() {
+        let app = TestApp::new();
+        let project = app.project("It's \"quoted\"\nover two lines");
+        let tree = app.tree(Some(&project.id), "Tree");
+        let root = app.node(&tree.id, None, "root");
+        let params = GenerationParams {
+            temperature: Some(0.5),
+            top_p: None,
+            max_tokens: Some(64),
+            seed: None,
+        };
+        let child = create_node(
+            app.state(),
+            CreateNode {
+                tokens: Some(3),
+                params: Some(params),
+                ..new_node(&tree.id, Some(&root.id), "needle's haystack")
+            },
+        )
+        .unwrap();
+        let file = TempPath::new("sql");
+
+        let bytes = export_sql_dump(app.state(), file.path(), None).unwrap();
+        let dump = file.read();
+        assert_eq!(bytes, dump.len() as u64);
+
+        let fresh = replay(&dump);
+        let name: String = fresh
+            .query_row(
+                "SELECT name FROM projects WHERE id = ?1",
+                [&project.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(name, project.name);
+        let nodes: i64 = fresh
+            .query_row("SELECT COUNT(*) FROM nodes", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(nodes, 2);
+        let hit: String = fresh
+            .query_row(
+                "SELECT n.id FROM nodes_fts
+                 INNER JOIN nodes n ON n.rowid = nodes_fts.rowid
+                 WHERE nodes_fts MATCH 'needle'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(hit, child.id);
+        let stored: String = fresh
+            .query_row(
+                "SELECT params FROM nodes WHERE id = ?1",
+                [&child.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let stored: GenerationParams = serde_json::from_str(&stored).unwrap();
+        assert_eq!(stored.max_tokens, Some(64));
+        let migrations: i64 = fresh
+            .query_row("SELECT COUNT(*) FROM _migrations", [], |row| row.get(0))
+            .unwrap();
+        assert!(migrations > 0);
+    }
+
+    #[test]
+    fn sql_dump_subset_takes_the_tables_it_needs_to_replay() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let root = app.node(&tree.id, None, "root");
+        let file = TempPath::new("sql");
+
+        export_sql_dump(app.state(), file.path(), Some(vec!["projects".to_string()])).unwrap();
+        let dump = file.read();
+        assert!(!dump.contains("CREATE TABLE nodes"));
+        replay(&dump);
+
+        for table in ["nodes", "nodes_fts"] {
+            export_sql_dump(app.state(), file.path(), Some(vec![table.to_string()])).unwrap();
+            let dump = file.read();
+            assert!(dump.contains("CREATE TABLE nodes"), "{table}");
+            assert!(dump.contains("CREATE VIRTUAL TABLE nodes_fts"), "{table}");
+
+            let fresh = replay(&dump);
+            let indexed: String = fresh
+                .query_row(
+                    "SELECT n.id FROM nodes_fts INNER JOIN nodes n ON n.rowid = nodes_fts.rowid",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(indexed, root.id);
+            // The full-text triggers came along and still have their index to write into
+            fresh
+                .execute("UPDATE nodes SET user_content = 'edited'", [])
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn sql_dump_rejects_unknown_and_shadow_tables() {
+        let app = TestApp::new();
+        let file = TempPath::new("sql");
+
+        for table in ["missing", "nodes_fts_data"] {
+            assert!(matches!(
+                export_sql_dump(app.state(), file.path(), Some(vec![table.to_string()])),
+                Err(AppError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn sync_state_fingerprint_changes_with_any_edit() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let node = app.node(&tree.id, None, "root");
+        set_setting(app.state(), "key".to_string(), "value".to_string()).unwrap();
+        // Backdate everything so the edit below is sure to move `updated_at`
+        for table in ["projects", "trees", "nodes", "settings"] {
+            app.execute(
+                &format!("UPDATE {table} SET updated_at = datetime('now', '-1 hour')"),
+                [],
+            );
+        }
+
+        let before = get_sync_state(app.state()).unwrap();
+        assert_eq!(
+            before.fingerprint,
+            get_sync_state(app.state()).unwrap().fingerprint
+        );
+        assert_eq!(before.fingerprint.len(), 16);
+        assert_eq!(before.nodes.count, 1);
+        assert_eq!(before.projects.count, 0);
+        assert!(before.projects.last_modified.is_none());
+
+        let patch = serde_json::from_str(r#"{"user_content": "edited"}"#).unwrap();
+        update_node(app.state(), node.id, patch).unwrap();
+
+        let after = get_sync_state(app.state()).unwrap();
+        assert_ne!(after.fingerprint, before.fingerprint);
+        assert_eq!(after.trees, before.trees);
+        assert_ne!(after.nodes, before.nodes);
+    }
+
+    /// Helper function to age every project, tree and node so later edits are newer
+    fn backdate(app: &TestApp) {
+        for table in ["projects", "trees", "nodes"] {
+            app.execute(
+                &format!("UPDATE {table} SET updated_at = datetime('now', '-1 hour')"),
+                [],
+            );
+        }
+    }
+
+    /// Helper function to copy the trees and nodes of one database file into another app
+    fn copy_rows(from: &TempPath, into: &TestApp) {
+        write_unchecked(
+            into,
+            &format!(
+                "ATTACH DATABASE '{}' AS source;
+                 INSERT INTO trees SELECT * FROM source.trees;
+                 INSERT INTO nodes SELECT * FROM source.nodes;
+                 DETACH DATABASE source;",
+                from.path()
+            ),
+        );
+    }
+
+    /// Helper function to check the `other` database was detached again
+    fn is_attached(app: &TestApp) -> bool {
+        app.state()
+            .db
+            .conn()
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM pragma_database_list WHERE name = 'other')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn merge_keeps_the_newer_row() {
+        let (here, there) = (TempPath::new("db"), TempPath::new("db"));
+        let (app, other) = (TestApp::open(&here), TestApp::open(&there));
+        let tree = app.tree(None, "Tree");
+        let root = app.node(&tree.id, None, "original");
+        set_setting(app.state(), "key".to_string(), "here".to_string()).unwrap();
+        backdate(&app);
+        app.execute(
+            "UPDATE settings SET updated_at = datetime('now', '-1 day')",
+            [],
+        );
+
+        copy_rows(&here, &other);
+        let patch = serde_json::from_str(r#"{"user_content": "newer"}"#).unwrap();
+        update_node(other.state(), root.id.clone(), patch).unwrap();
+        let child = other.node(&tree.id, Some(&root.id), "child");
+        let new_tree = other.tree(None, "New");
+        other.node(&new_tree.id, None, "new root");
+        set_setting(other.state(), "key".to_string(), "there".to_string()).unwrap();
+
+        let report =
+            merge_database(app.state(), there.path(), MergeStrategy::LastWriterWins).unwrap();
+
+        let counts = |inserted, updated, skipped| TableMergeCounts {
+            inserted,
+            updated,
+            skipped,
+        };
+        assert_eq!(report.trees, counts(1, 0, 1));
+        assert_eq!(report.nodes, counts(2, 1, 0));
+        assert_eq!(report.settings.updated, 1);
+        assert_eq!(
+            get_node(app.state(), root.id.clone()).unwrap().user_content,
+            "newer"
+        );
+        let child = get_node(app.state(), child.id).unwrap();
+        assert_eq!(child.parent_id, Some(root.id));
+        assert_eq!(
+            get_setting(app.state(), "key".to_string()).unwrap().value,
+            "there"
+        );
+        let indexed: i64 = app
+            .state()
+            .db
+            .conn()
+            .query_row(
+                "SELECT COUNT(*) FROM nodes_fts WHERE nodes_fts MATCH 'newer'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(indexed, 1);
+        assert!(!is_attached(&app));
+
+        // Merging the same file again has nothing left to do
+        let report =
+            merge_database(app.state(), there.path(), MergeStrategy::LastWriterWins).unwrap();
+        assert_eq!(report.nodes, counts(0, 0, 3));
+        assert!(matches!(
+            merge_database(
+                app.state(),
+                "/missing.db".to_string(),
+                MergeStrategy::KeepBoth
+            ),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn merge_keeping_both_copies_whole_subtrees() {
+        let (here, there) = (TempPath::new("db"), TempPath::new("db"));
+        let (app, other) = (TestApp::open(&here), TestApp::open(&there));
+        let tree = app.tree(None, "Tree");
+        let root = app.node(&tree.id, None, "root");
+        let middle = app.node(&tree.id, Some(&root.id), "middle");
+        let leaf = app.node(&tree.id, Some(&middle.id), "leaf");
+        backdate(&app);
+
+        copy_rows(&here, &other);
+        let rename = serde_json::from_str(r#"{"name": "Edited"}"#).unwrap();
+        update_tree(other.state(), tree.id.clone(), rename).unwrap();
+        let patch = serde_json::from_str(r#"{"user_content": "edited"}"#).unwrap();
+        update_node(other.state(), middle.id.clone(), patch).unwrap();
+
+        let report = merge_database(app.state(), there.path(), MergeStrategy::KeepBoth).unwrap();
+
+        assert_eq!(report.trees.inserted, 1);
+        assert_eq!(report.nodes.inserted, 3);
+        assert_eq!(get_tree(app.state(), tree.id.clone()).unwrap().name, "Tree");
+        assert_eq!(
+            get_node(app.state(), middle.id.clone())
+                .unwrap()
+                .user_content,
+            "middle"
+        );
+        assert_eq!(list_nodes(app.state(), tree.id.clone()).unwrap().len(), 3);
+
+        let copy_id: String = app
+            .state()
+            .db
+            .conn()
+            .query_row("SELECT id FROM trees WHERE name = 'Edited'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        let copies = list_nodes(app.state(), copy_id).unwrap();
+        assert_eq!(copies.len(), 3);
+        let originals = [root.id, middle.id, leaf.id];
+        for copy in &copies {
+            assert!(!originals.contains(&copy.id));
+            if let Some(ref parent_id) = copy.parent_id {
+                assert!(copies.iter().any(|node| &node.id == parent_id));
+            }
+        }
+        assert!(copies.iter().any(|node| node.user_content == "edited"));
+        let crossed: i64 = app
+            .state()
+            .db
+            .conn()
+            .query_row(
+                "SELECT COUNT(*) FROM nodes child
+                 INNER JOIN nodes parent ON parent.id = child.parent_id
+                 WHERE parent.tree_id <> child.tree_id",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(crossed, 0);
+    }
+
+    #[test]
+    fn merge_rejects_a_different_schema_version() {
+        let (there, plain) = (TempPath::new("db"), TempPath::new("db"));
+        let app = TestApp::new();
+        let other = TestApp::open(&there);
+        other.execute(
+            "DELETE FROM _migrations WHERE name = '010_add_node_content_hash'",
+            [],
+        );
+        Connection::open(plain.path())
+            .unwrap()
+            .execute_batch("CREATE TABLE notes (body TEXT);")
+            .unwrap();
+
+        for path in [there.path(), plain.path()] {
+            assert!(matches!(
+                merge_database(app.state(), path, MergeStrategy::LastWriterWins),
+                Err(AppError::InvalidInput(_))
+            ));
+            assert!(!is_attached(&app));
+        }
+    }
+}
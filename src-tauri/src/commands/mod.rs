@@ -1,9 +1,16 @@
+pub mod database;
 pub mod nodes;
 pub mod projects;
+pub mod search;
 pub mod settings;
 pub mod trees;
 
+#[cfg(test)]
+mod test_support;
+
+pub use database::*;
 pub use nodes::*;
 pub use projects::*;
+pub use search::*;
 pub use settings::*;
 pub use trees::*;
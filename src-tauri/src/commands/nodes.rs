@@ -1,19 +1,31 @@
+use crate::db::hash::{content_hash, normalize_content};
 use crate::error::{AppError, Result};
-use crate::models::{CreateNode, Node, UpdateNode};
+use crate::models::{
+    Adjacency, BranchPreview, CreateNode, DailyUsage, DeleteReason, GenerationParams, Node,
+    NodeContext, NodeWithTree, PurgeReport, ReadingTime, TokenShare, UpdateNode,
+};
 use crate::AppState;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tauri::State;
 use uuid::Uuid;
 
 /// Create a new node
+/// If no model is given, the tree's default model is used. Reusing an idempotency key
+/// returns the node first created with it, as long as the tree and parent match and
+/// that node has not been deleted.
 #[tauri::command]
 pub fn create_node(state: State<Arc<AppState>>, input: CreateNode) -> Result<Node> {
     let conn = state.db.conn();
     let id = Uuid::new_v4().to_string();
 
-    conn.execute(
-        "INSERT INTO nodes (id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    let params = input.params.as_ref().map(params_to_json).transpose()?;
+    let content_hash = content_hash(&input.user_content, input.assistant_content.as_deref());
+
+    let rows_affected = conn.execute(
+        "INSERT INTO nodes (id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, params, idempotency_key, content_hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, COALESCE(?7, (SELECT default_model FROM trees WHERE id = ?2)), ?8, ?9, ?10, ?11)
+         ON CONFLICT (idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING",
         (
             &id,
             &input.tree_id,
@@ -23,9 +35,59 @@ pub fn create_node(state: State<Arc<AppState>>, input: CreateNode) -> Result<Nod
             &input.summary,
             &input.model,
             &input.tokens,
+            &params,
+            &input.idempotency_key,
+            &content_hash,
         ),
     )?;
 
+    // A retried create with an already-used key returns the node it created the first time
+    if rows_affected == 0 {
+        if let Some(ref key) = input.idempotency_key {
+            let existing_id: String = conn.query_row(
+                "SELECT id FROM nodes WHERE idempotency_key = ?1",
+                [key],
+                |row| row.get(0),
+            )?;
+            let existing = get_node_by_id(&conn, &existing_id)?;
+            if existing.tree_id != input.tree_id || existing.parent_id != input.parent_id {
+                return Err(AppError::InvalidInput(format!(
+                    "Idempotency key '{key}' was already used for a different turn"
+                )));
+            }
+            if existing.deleted_at.is_some() {
+                return Err(AppError::InvalidInput(format!(
+                    "Idempotency key '{key}' belongs to a deleted node"
+                )));
+            }
+            return Ok(existing);
+        }
+    }
+
+    get_node_by_id(&conn, &id)
+}
+
+/// Create a sibling of a node that repeats its prompt without a response
+/// The variant takes the tree's default model, like any new turn.
+#[tauri::command]
+pub fn create_variant(state: State<Arc<AppState>>, node_id: String) -> Result<Node> {
+    let conn = state.db.conn();
+    let id = Uuid::new_v4().to_string();
+
+    let rows_affected = conn.execute(
+        "INSERT INTO nodes (id, tree_id, parent_id, user_content, model)
+         SELECT ?1, n.tree_id, n.parent_id, n.user_content, t.default_model
+         FROM nodes n
+         INNER JOIN trees t ON t.id = n.tree_id
+         WHERE n.id = ?2 AND n.deleted_at IS NULL",
+        (&id, &node_id),
+    )?;
+
+    if rows_affected == 0 {
+        return Err(AppError::NotFound(format!("Node {node_id} not found")));
+    }
+    refresh_content_hash(&conn, &id)?;
+
     get_node_by_id(&conn, &id)
 }
 
@@ -36,13 +98,33 @@ pub fn get_node(state: State<Arc<AppState>>, id: String) -> Result<Node> {
     get_node_by_id(&conn, &id)
 }
 
+/// Find the oldest active node whose normalized content has the given hash
+#[tauri::command]
+pub fn find_node_by_hash(state: State<Arc<AppState>>, hash: String) -> Result<Option<Node>> {
+    let conn = state.db.conn();
+    let id = conn.query_row(
+        "SELECT id FROM nodes
+         WHERE content_hash = ?1 AND deleted_at IS NULL
+         ORDER BY created_at ASC, rowid ASC
+         LIMIT 1",
+        [&hash],
+        |row| row.get::<_, String>(0),
+    );
+
+    match id {
+        Ok(id) => get_node_by_id(&conn, &id).map(Some),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(AppError::Database(e)),
+    }
+}
+
 /// List all active (non-deleted) nodes in a tree
 #[tauri::command]
 pub fn list_nodes(state: State<Arc<AppState>>, tree_id: String) -> Result<Vec<Node>> {
     let conn = state.db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed
+        "SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed, delete_reason_id, params, content_hash
          FROM nodes
          WHERE tree_id = ?1 AND deleted_at IS NULL
          ORDER BY created_at ASC",
@@ -55,13 +137,86 @@ pub fn list_nodes(state: State<Arc<AppState>>, tree_id: String) -> Result<Vec<No
     Ok(nodes)
 }
 
+/// Maximum number of tree IDs bound in a single `IN (...)` query, kept well under
+/// the database host parameter limit
+const TREE_ID_CHUNK_SIZE: usize = 500;
+
+/// List all active (non-deleted) nodes across several trees
+/// Nodes are grouped by tree ID and ordered by creation time within each tree
+#[tauri::command]
+pub fn list_nodes_for_trees(
+    state: State<Arc<AppState>>,
+    tree_ids: Vec<String>,
+) -> Result<Vec<Node>> {
+    let conn = state.db.conn();
+
+    // Sorting the IDs up front keeps the concatenated chunks in tree_id order
+    let mut tree_ids = tree_ids;
+    tree_ids.sort();
+    tree_ids.dedup();
+
+    let mut nodes = Vec::new();
+    for chunk in tree_ids.chunks(TREE_ID_CHUNK_SIZE) {
+        let placeholders = (1..=chunk.len())
+            .map(|i| format!("?{i}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let query = format!(
+            "SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed, delete_reason_id, params, content_hash
+             FROM nodes
+             WHERE tree_id IN ({placeholders}) AND deleted_at IS NULL
+             ORDER BY tree_id ASC, created_at ASC"
+        );
+
+        let mut stmt = conn.prepare(&query)?;
+        let chunk_nodes = stmt
+            .query_map(rusqlite::params_from_iter(chunk), map_node)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        nodes.extend(chunk_nodes);
+    }
+
+    Ok(nodes)
+}
+
+/// List active nodes generated by a model (case-insensitive), newest first
+#[tauri::command]
+pub fn list_nodes_by_model(
+    state: State<Arc<AppState>>,
+    model: String,
+    limit: u32,
+) -> Result<Vec<NodeWithTree>> {
+    let conn = state.db.conn();
+
+    let mut stmt = conn.prepare(
+        "SELECT n.id, n.tree_id, n.parent_id, n.user_content, n.assistant_content, n.summary, n.model, n.tokens, n.created_at, n.updated_at, n.deleted_at, n.failed, n.delete_reason_id, n.params, n.content_hash, t.name
+         FROM nodes n
+         INNER JOIN trees t ON t.id = n.tree_id
+         WHERE n.model = ?1 COLLATE NOCASE
+           AND n.deleted_at IS NULL
+           AND t.deleted_at IS NULL
+         ORDER BY n.created_at DESC
+         LIMIT ?2",
+    )?;
+
+    let nodes = stmt
+        .query_map((&model, limit), |row| {
+            Ok(NodeWithTree {
+                node: map_node(row)?,
+                tree_name: row.get(15)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(nodes)
+}
+
 /// Get root nodes (nodes without a parent) in a tree
 #[tauri::command]
 pub fn get_root_nodes(state: State<Arc<AppState>>, tree_id: String) -> Result<Vec<Node>> {
     let conn = state.db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed
+        "SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed, delete_reason_id, params, content_hash
          FROM nodes
          WHERE tree_id = ?1 AND parent_id IS NULL AND deleted_at IS NULL
          ORDER BY created_at ASC",
@@ -74,13 +229,44 @@ pub fn get_root_nodes(state: State<Arc<AppState>>, tree_id: String) -> Result<Ve
     Ok(nodes)
 }
 
+/// Get a node together with its parent and live children
+#[tauri::command]
+pub fn get_node_context(state: State<Arc<AppState>>, id: String) -> Result<NodeContext> {
+    let conn = state.db.conn();
+
+    let node = get_node_by_id(&conn, &id)?;
+    // Like the children, a trashed parent is left out
+    let parent = match node.parent_id {
+        Some(ref parent_id) => {
+            Some(get_node_by_id(&conn, parent_id)?).filter(|parent| parent.deleted_at.is_none())
+        }
+        None => None,
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed, delete_reason_id, params, content_hash
+         FROM nodes
+         WHERE parent_id = ?1 AND deleted_at IS NULL
+         ORDER BY created_at ASC",
+    )?;
+    let children = stmt
+        .query_map([&id], map_node)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(NodeContext {
+        node,
+        parent,
+        children,
+    })
+}
+
 /// Get children of a node
 #[tauri::command]
 pub fn get_child_nodes(state: State<Arc<AppState>>, parent_id: String) -> Result<Vec<Node>> {
     let conn = state.db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed
+        "SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed, delete_reason_id, params, content_hash
          FROM nodes
          WHERE parent_id = ?1 AND deleted_at IS NULL
          ORDER BY created_at ASC",
@@ -93,38 +279,282 @@ pub fn get_child_nodes(state: State<Arc<AppState>>, parent_id: String) -> Result
     Ok(nodes)
 }
 
+/// Key used in the branch map for nodes without a parent
+pub const BRANCH_MAP_ROOT_KEY: &str = "root";
+
+/// Get the topology of a tree as a map of parent ID to ordered child IDs
+/// Root nodes are listed under `BRANCH_MAP_ROOT_KEY`
+#[tauri::command]
+pub fn get_branch_map(
+    state: State<Arc<AppState>>,
+    tree_id: String,
+) -> Result<HashMap<String, Vec<String>>> {
+    let conn = state.db.conn();
+
+    let mut stmt = conn.prepare(
+        "SELECT id, parent_id
+         FROM nodes
+         WHERE tree_id = ?1 AND deleted_at IS NULL
+         ORDER BY created_at ASC, rowid ASC",
+    )?;
+
+    let mut branches: HashMap<String, Vec<String>> = HashMap::new();
+    let rows = stmt.query_map([&tree_id], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+    })?;
+    for row in rows {
+        let (id, parent_id) = row?;
+        let key = parent_id.unwrap_or_else(|| BRANCH_MAP_ROOT_KEY.to_string());
+        branches.entry(key).or_default().push(id);
+    }
+
+    Ok(branches)
+}
+
+/// Get the topology of a tree as index-based edges between positions in `ids`
+/// Roots have no incoming edge, and neither do nodes whose parent is not active.
+#[tauri::command]
+pub fn get_tree_adjacency(state: State<Arc<AppState>>, tree_id: String) -> Result<Adjacency> {
+    let conn = state.db.conn();
+
+    let mut stmt = conn.prepare(
+        "SELECT id, parent_id
+         FROM nodes
+         WHERE tree_id = ?1 AND deleted_at IS NULL
+         ORDER BY created_at ASC, rowid ASC",
+    )?;
+    let rows = stmt
+        .query_map([&tree_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let index: HashMap<&str, usize> = rows
+        .iter()
+        .enumerate()
+        .map(|(i, (id, _))| (id.as_str(), i))
+        .collect();
+    let edges = rows
+        .iter()
+        .enumerate()
+        .filter_map(|(child, (_, parent_id))| {
+            let parent = *index.get(parent_id.as_deref()?)?;
+            Some((parent, child))
+        })
+        .collect();
+    let ids = rows.into_iter().map(|(id, _)| id).collect();
+
+    Ok(Adjacency { ids, edges })
+}
+
+/// Maximum length, in characters, of a content snippet in a branch preview
+const PREVIEW_SNIPPET_CHARS: usize = 120;
+
+/// Get previews of the other branches at a node's turn (its active siblings)
+/// Works for root nodes too, where siblings are the tree's other roots
+#[tauri::command]
+pub fn get_branch_alternatives(
+    state: State<Arc<AppState>>,
+    node_id: String,
+) -> Result<Vec<BranchPreview>> {
+    let conn = state.db.conn();
+
+    let node = get_node_by_id(&conn, &node_id)?;
+
+    let siblings = {
+        let mut stmt = conn.prepare(
+            "SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed, delete_reason_id, params, content_hash
+             FROM nodes
+             WHERE tree_id = ?1 AND parent_id IS ?2 AND id != ?3 AND deleted_at IS NULL
+             ORDER BY created_at ASC",
+        )?;
+        let siblings = stmt
+            .query_map((&node.tree_id, &node.parent_id, &node_id), map_node)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        siblings
+    };
+
+    let mut previews = Vec::with_capacity(siblings.len());
+    for sibling in siblings {
+        let first_child = conn.query_row(
+            "SELECT summary, user_content
+             FROM nodes
+             WHERE parent_id = ?1 AND deleted_at IS NULL
+             ORDER BY created_at ASC
+             LIMIT 1",
+            [&sibling.id],
+            |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, String>(1)?)),
+        );
+        let first_child_preview = match first_child {
+            Ok((summary, user_content)) => {
+                Some(summary.unwrap_or_else(|| snippet(&user_content, PREVIEW_SNIPPET_CHARS)))
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(AppError::Database(e)),
+        };
+
+        let descendant_count = conn.query_row(
+            "WITH RECURSIVE descendants AS (
+                SELECT id FROM nodes WHERE parent_id = ?1 AND deleted_at IS NULL
+                UNION ALL
+                SELECT n.id
+                FROM nodes n
+                INNER JOIN descendants d ON n.parent_id = d.id
+                WHERE n.deleted_at IS NULL
+            )
+            SELECT COUNT(*) FROM descendants",
+            [&sibling.id],
+            |row| row.get(0),
+        )?;
+
+        previews.push(BranchPreview {
+            sibling_node: sibling,
+            first_child_preview,
+            descendant_count,
+        });
+    }
+
+    Ok(previews)
+}
+
 /// Get the path from a node to the root (for context building)
 /// Returns nodes in order from root to the specified node
 #[tauri::command]
 pub fn get_node_path(state: State<Arc<AppState>>, node_id: String) -> Result<Vec<Node>> {
     let conn = state.db.conn();
+    query_node_path(&conn, &node_id)
+}
+
+/// Get the ids of the nodes from the root to a node (inclusive)
+/// Lighter than `get_node_path` for breadcrumbs that don't need content.
+#[tauri::command]
+pub fn get_ancestor_ids(state: State<Arc<AppState>>, node_id: String) -> Result<Vec<String>> {
+    let conn = state.db.conn();
 
-    // Use recursive CTE to traverse up the tree
     let mut stmt = conn.prepare(
         "WITH RECURSIVE path AS (
-            SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed, 0 as depth
+            SELECT id, parent_id, 0 as depth
             FROM nodes
             WHERE id = ?1 AND deleted_at IS NULL
             UNION ALL
-            SELECT n.id, n.tree_id, n.parent_id, n.user_content, n.assistant_content, n.summary, n.model, n.tokens, n.created_at, n.updated_at, n.deleted_at, n.failed, p.depth + 1
+            SELECT n.id, n.parent_id, p.depth + 1
             FROM nodes n
             INNER JOIN path p ON n.id = p.parent_id
             WHERE n.deleted_at IS NULL
         )
-        SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed
-        FROM path
-        ORDER BY depth DESC",
+        SELECT id FROM path ORDER BY depth DESC",
     )?;
 
-    let nodes = stmt
-        .query_map([&node_id], map_node)?
-        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let ids = stmt
+        .query_map([&node_id], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<String>, _>>()?;
 
-    if nodes.is_empty() {
+    if ids.is_empty() {
         return Err(AppError::NotFound(format!("Node {node_id} not found")));
     }
 
-    Ok(nodes)
+    Ok(ids)
+}
+
+/// Render the path from the root to a node as a plain-text transcript
+/// Each message is labeled and messages are joined by `separator` (a blank line by
+/// default). Failed turns and empty assistant replies are left out.
+#[tauri::command]
+pub fn render_path_text(
+    state: State<Arc<AppState>>,
+    leaf_node_id: String,
+    separator: Option<String>,
+) -> Result<String> {
+    let conn = state.db.conn();
+    let path = query_node_path(&conn, &leaf_node_id)?;
+
+    let mut parts = Vec::new();
+    for node in path.iter().filter(|node| !node.failed) {
+        parts.push(format!("User: {}", node.user_content));
+        if let Some(assistant_content) = node
+            .assistant_content
+            .as_deref()
+            .filter(|content| !content.trim().is_empty())
+        {
+            parts.push(format!("Assistant: {assistant_content}"));
+        }
+    }
+
+    Ok(parts.join(separator.as_deref().unwrap_or("\n\n")))
+}
+
+/// Reading speed used when none is given, in words per minute
+const DEFAULT_WORDS_PER_MINUTE: u32 = 200;
+
+/// Estimate how long it takes to read the path from the root to a node
+/// Failed turns are not counted
+#[tauri::command]
+pub fn get_path_reading_time(
+    state: State<Arc<AppState>>,
+    leaf_node_id: String,
+    wpm: Option<u32>,
+) -> Result<ReadingTime> {
+    let wpm = wpm.unwrap_or(DEFAULT_WORDS_PER_MINUTE);
+    if wpm == 0 {
+        return Err(AppError::InvalidInput(
+            "Words per minute must be positive".to_string(),
+        ));
+    }
+
+    let conn = state.db.conn();
+    let path = query_node_path(&conn, &leaf_node_id)?;
+
+    let word_count: usize = path
+        .iter()
+        .filter(|node| !node.failed)
+        .map(|node| {
+            node.user_content.split_whitespace().count()
+                + node
+                    .assistant_content
+                    .as_deref()
+                    .map_or(0, |content| content.split_whitespace().count())
+        })
+        .sum();
+    let word_count = u32::try_from(word_count).unwrap_or(u32::MAX);
+
+    Ok(ReadingTime {
+        word_count,
+        minutes: f64::from(word_count) / f64::from(wpm),
+    })
+}
+
+/// Get the longest root-to-leaf path in a tree
+/// Ties are broken by total tokens, then by the most recently created leaf.
+/// Returns nodes in order from root to leaf, or an empty list for an empty tree.
+#[tauri::command]
+pub fn get_longest_branch(state: State<Arc<AppState>>, tree_id: String) -> Result<Vec<Node>> {
+    let conn = state.db.conn();
+
+    // Walk down from the roots; the deepest node reached is always a leaf
+    let leaf_id = conn.query_row(
+        "WITH RECURSIVE walk AS (
+            SELECT id, 1 AS depth, COALESCE(tokens, 0) AS total_tokens, created_at
+            FROM nodes
+            WHERE tree_id = ?1 AND parent_id IS NULL AND deleted_at IS NULL
+            UNION ALL
+            SELECT n.id, w.depth + 1, w.total_tokens + COALESCE(n.tokens, 0), n.created_at
+            FROM nodes n
+            INNER JOIN walk w ON n.parent_id = w.id
+            WHERE n.deleted_at IS NULL
+        )
+        SELECT id
+        FROM walk
+        ORDER BY depth DESC, total_tokens DESC, created_at DESC
+        LIMIT 1",
+        [&tree_id],
+        |row| row.get::<_, String>(0),
+    );
+
+    match leaf_id {
+        Ok(leaf_id) => query_node_path(&conn, &leaf_id),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(Vec::new()),
+        Err(e) => Err(AppError::Database(e)),
+    }
 }
 
 /// Get all leaf nodes in a tree (nodes without children)
@@ -133,7 +563,7 @@ pub fn get_leaf_nodes(state: State<Arc<AppState>>, tree_id: String) -> Result<Ve
     let conn = state.db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT n.id, n.tree_id, n.parent_id, n.user_content, n.assistant_content, n.summary, n.model, n.tokens, n.created_at, n.updated_at, n.deleted_at, n.failed
+        "SELECT n.id, n.tree_id, n.parent_id, n.user_content, n.assistant_content, n.summary, n.model, n.tokens, n.created_at, n.updated_at, n.deleted_at, n.failed, n.delete_reason_id, n.params, n.content_hash
          FROM nodes n
          WHERE n.tree_id = ?1
            AND n.deleted_at IS NULL
@@ -151,10 +581,168 @@ pub fn get_leaf_nodes(state: State<Arc<AppState>>, tree_id: String) -> Result<Ve
     Ok(nodes)
 }
 
+/// Get unanswered, non-failed leaf nodes in a tree, deepest first
+/// These are the natural places to resume generating.
+#[tauri::command]
+pub fn list_resume_candidates(state: State<Arc<AppState>>, tree_id: String) -> Result<Vec<Node>> {
+    let conn = state.db.conn();
+
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE walk AS (
+            SELECT id, 0 AS depth
+            FROM nodes
+            WHERE tree_id = ?1 AND parent_id IS NULL AND deleted_at IS NULL
+            UNION ALL
+            SELECT n.id, w.depth + 1
+            FROM nodes n
+            INNER JOIN walk w ON n.parent_id = w.id
+            WHERE n.deleted_at IS NULL
+        )
+        SELECT n.id, n.tree_id, n.parent_id, n.user_content, n.assistant_content, n.summary, n.model, n.tokens, n.created_at, n.updated_at, n.deleted_at, n.failed, n.delete_reason_id, n.params, n.content_hash
+        FROM walk w
+        INNER JOIN nodes n ON n.id = w.id
+        WHERE n.failed = 0
+          AND (n.assistant_content IS NULL OR TRIM(n.assistant_content) = '')
+          AND NOT EXISTS (
+              SELECT 1 FROM nodes child
+              WHERE child.parent_id = n.id AND child.deleted_at IS NULL
+          )
+        ORDER BY w.depth DESC, n.created_at DESC",
+    )?;
+
+    let nodes = stmt
+        .query_map([&tree_id], map_node)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(nodes)
+}
+
+/// Count the distinct root-to-leaf branches in a tree (one per active leaf)
+#[tauri::command]
+pub fn count_branches(state: State<Arc<AppState>>, tree_id: String) -> Result<i64> {
+    let conn = state.db.conn();
+
+    let count = conn.query_row(
+        "SELECT COUNT(*)
+         FROM nodes n
+         WHERE n.tree_id = ?1
+           AND n.deleted_at IS NULL
+           AND NOT EXISTS (
+               SELECT 1 FROM nodes child
+               WHERE child.parent_id = n.id AND child.deleted_at IS NULL
+           )",
+        [&tree_id],
+        |row| row.get(0),
+    )?;
+
+    Ok(count)
+}
+
+/// Longest span, in days, that `get_daily_token_usage` will report on
+const MAX_USAGE_DAYS: u32 = 3660;
+
+/// Get tokens and node counts per creation day over the last `days` days (including today)
+/// Optionally scoped to a project; days without activity are returned with zero counts.
+#[tauri::command]
+pub fn get_daily_token_usage(
+    state: State<Arc<AppState>>,
+    project_id: Option<String>,
+    days: u32,
+) -> Result<Vec<DailyUsage>> {
+    if days == 0 {
+        return Ok(Vec::new());
+    }
+    if days > MAX_USAGE_DAYS {
+        return Err(AppError::InvalidInput(format!(
+            "Usage can cover at most {MAX_USAGE_DAYS} days"
+        )));
+    }
+
+    let conn = state.db.conn();
+    let start = format!("-{} days", days - 1);
+
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE calendar(day) AS (
+            SELECT date('now', ?1)
+            UNION ALL
+            SELECT date(day, '+1 day') FROM calendar WHERE day < date('now')
+        ),
+        usage AS (
+            SELECT strftime('%Y-%m-%d', n.created_at) AS day,
+                   SUM(COALESCE(n.tokens, 0)) AS total_tokens,
+                   COUNT(*) AS node_count
+            FROM nodes n
+            INNER JOIN trees t ON t.id = n.tree_id
+            WHERE n.deleted_at IS NULL
+              AND t.deleted_at IS NULL
+              AND (?2 IS NULL OR t.project_id = ?2)
+              AND n.created_at >= date('now', ?1)
+            GROUP BY 1
+        )
+        SELECT c.day, COALESCE(u.total_tokens, 0), COALESCE(u.node_count, 0)
+        FROM calendar c
+        LEFT JOIN usage u ON u.day = c.day
+        ORDER BY c.day ASC",
+    )?;
+
+    let usage = stmt
+        .query_map((&start, &project_id), |row| {
+            Ok(DailyUsage {
+                date: row.get(0)?,
+                total_tokens: row.get(1)?,
+                node_count: row.get(2)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(usage)
+}
+
+/// Get each active node's share of the tree's total tokens
+/// Nodes without a token count are treated as zero; if the tree has no tokens at all,
+/// every fraction is zero
+#[tauri::command]
+pub fn get_token_distribution(
+    state: State<Arc<AppState>>,
+    tree_id: String,
+) -> Result<Vec<TokenShare>> {
+    let conn = state.db.conn();
+
+    let mut stmt = conn.prepare(
+        "SELECT id, COALESCE(tokens, 0)
+         FROM nodes
+         WHERE tree_id = ?1 AND deleted_at IS NULL
+         ORDER BY created_at ASC",
+    )?;
+
+    let counts = stmt
+        .query_map([&tree_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let total: f64 = counts.iter().map(|(_, tokens)| f64::from(*tokens)).sum();
+
+    let shares = counts
+        .into_iter()
+        .map(|(node_id, tokens)| TokenShare {
+            node_id,
+            tokens,
+            fraction: if total > 0.0 {
+                f64::from(tokens) / total
+            } else {
+                0.0
+            },
+        })
+        .collect();
+
+    Ok(shares)
+}
+
 /// Update a node
 #[tauri::command]
 pub fn update_node(state: State<Arc<AppState>>, id: String, input: UpdateNode) -> Result<Node> {
-    let conn = state.db.conn();
+    let mut conn = state.db.conn();
 
     // Check if node exists and is not deleted
     let existing = get_node_by_id(&conn, &id)?;
@@ -166,23 +754,24 @@ pub fn update_node(state: State<Arc<AppState>>, id: String, input: UpdateNode) -
     let mut updates = vec!["updated_at = datetime('now')".to_string()];
     let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![];
 
+    let content_changed = input.user_content.is_some() || !input.assistant_content.is_unchanged();
     if let Some(ref user_content) = input.user_content {
         updates.push(format!("user_content = ?{}", params.len() + 1));
         params.push(Box::new(user_content.clone()));
     }
-    if let Some(ref assistant_content) = input.assistant_content {
+    if let Some(assistant_content) = input.assistant_content.into_change() {
         updates.push(format!("assistant_content = ?{}", params.len() + 1));
-        params.push(Box::new(assistant_content.clone()));
+        params.push(Box::new(assistant_content));
     }
-    if let Some(ref summary) = input.summary {
+    if let Some(summary) = input.summary.into_change() {
         updates.push(format!("summary = ?{}", params.len() + 1));
-        params.push(Box::new(summary.clone()));
+        params.push(Box::new(summary));
     }
-    if let Some(ref model) = input.model {
+    if let Some(model) = input.model.into_change() {
         updates.push(format!("model = ?{}", params.len() + 1));
-        params.push(Box::new(model.clone()));
+        params.push(Box::new(model));
     }
-    if let Some(tokens) = input.tokens {
+    if let Some(tokens) = input.tokens.into_change() {
         updates.push(format!("tokens = ?{}", params.len() + 1));
         params.push(Box::new(tokens));
     }
@@ -200,19 +789,224 @@ pub fn update_node(state: State<Arc<AppState>>, id: String, input: UpdateNode) -
 
     let params_refs: Vec<&dyn rusqlite::ToSql> =
         params.iter().map(std::convert::AsRef::as_ref).collect();
-    conn.execute(&query, params_refs.as_slice())?;
+    let tx = conn.transaction()?;
+    tx.execute(&query, params_refs.as_slice())?;
+    if content_changed {
+        refresh_content_hash(&tx, &id)?;
+    }
+    tx.commit()?;
 
     get_node_by_id(&conn, &id)
 }
 
-/// Soft delete a node (move to trash)
+/// Tidy whitespace in the content of a tree's active nodes
+/// Strips trailing whitespace from each line and collapses runs of blank lines into one.
+/// Returns the number of nodes whose content changed.
 #[tauri::command]
-pub fn delete_node(state: State<Arc<AppState>>, id: String) -> Result<Node> {
+pub fn normalize_whitespace(state: State<Arc<AppState>>, tree_id: String) -> Result<usize> {
+    let mut conn = state.db.conn();
+    let tx = conn.transaction()?;
+
+    let rows = {
+        let mut stmt = tx.prepare(
+            "SELECT id, user_content, assistant_content
+             FROM nodes
+             WHERE tree_id = ?1 AND deleted_at IS NULL",
+        )?;
+        let rows = stmt
+            .query_map([&tree_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        rows
+    };
+
+    let mut changed = 0;
+    for (id, user_content, assistant_content) in rows {
+        let new_user_content = normalize_content(&user_content);
+        let new_assistant_content = assistant_content.as_deref().map(normalize_content);
+
+        if new_user_content != user_content || new_assistant_content != assistant_content {
+            tx.execute(
+                "UPDATE nodes SET user_content = ?1, assistant_content = ?2, content_hash = ?3, updated_at = datetime('now') WHERE id = ?4",
+                (
+                    &new_user_content,
+                    &new_assistant_content,
+                    content_hash(&new_user_content, new_assistant_content.as_deref()),
+                    &id,
+                ),
+            )?;
+            changed += 1;
+        }
+    }
+
+    tx.commit()?;
+
+    Ok(changed)
+}
+
+/// Get the generation parameters recorded for a node
+#[tauri::command]
+pub fn get_node_params(
+    state: State<Arc<AppState>>,
+    id: String,
+) -> Result<Option<GenerationParams>> {
     let conn = state.db.conn();
+    Ok(get_node_by_id(&conn, &id)?.params)
+}
+
+/// Record (or clear) the generation parameters for a node
+#[tauri::command]
+pub fn set_node_params(
+    state: State<Arc<AppState>>,
+    id: String,
+    params: Option<GenerationParams>,
+) -> Result<Node> {
+    let conn = state.db.conn();
+
+    let params = params.as_ref().map(params_to_json).transpose()?;
 
     let rows_affected = conn.execute(
-        "UPDATE nodes SET deleted_at = datetime('now'), updated_at = datetime('now') WHERE id = ?1 AND deleted_at IS NULL",
-        (&id,),
+        "UPDATE nodes SET params = ?1, updated_at = datetime('now') WHERE id = ?2 AND deleted_at IS NULL",
+        (&params, &id),
+    )?;
+
+    if rows_affected == 0 {
+        return Err(AppError::NotFound(format!("Node {id} not found")));
+    }
+
+    get_node_by_id(&conn, &id)
+}
+
+/// Mark many active nodes as failed at once
+/// Returns the number of nodes newly marked; IDs that are missing, deleted or already
+/// failed are skipped
+#[tauri::command]
+pub fn mark_nodes_failed(state: State<Arc<AppState>>, ids: Vec<String>) -> Result<usize> {
+    let mut conn = state.db.conn();
+    let tx = conn.transaction()?;
+
+    let mut updated = 0;
+    let mut seen = HashSet::new();
+    for id in ids.iter().filter(|id| seen.insert(id.as_str())) {
+        updated += tx.execute(
+            "UPDATE nodes SET failed = 1, updated_at = datetime('now') WHERE id = ?1 AND deleted_at IS NULL AND failed = 0",
+            (id,),
+        )?;
+    }
+
+    tx.commit()?;
+
+    Ok(updated)
+}
+
+/// Move a node and every later sibling under a new parent (or to the root level)
+/// Siblings are ordered by creation time. Returns the moved nodes in that order.
+#[tauri::command]
+pub fn move_siblings_from(
+    state: State<Arc<AppState>>,
+    node_id: String,
+    new_parent_id: Option<String>,
+) -> Result<Vec<Node>> {
+    let mut conn = state.db.conn();
+
+    let node = get_node_by_id(&conn, &node_id)?;
+    if node.deleted_at.is_some() {
+        return Err(AppError::NotFound(format!("Node {node_id} is deleted")));
+    }
+
+    let moved_ids: Vec<String> = {
+        let mut stmt = conn.prepare(
+            "SELECT id
+             FROM nodes
+             WHERE tree_id = ?1 AND parent_id IS ?2 AND deleted_at IS NULL
+             ORDER BY created_at ASC, rowid ASC",
+        )?;
+        let sibling_ids = stmt
+            .query_map((&node.tree_id, &node.parent_id), |row| row.get(0))?
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+        sibling_ids
+            .into_iter()
+            .skip_while(|id| *id != node_id)
+            .collect()
+    };
+
+    if let Some(ref parent_id) = new_parent_id {
+        let parent = get_node_by_id(&conn, parent_id)?;
+        if parent.deleted_at.is_some() {
+            return Err(AppError::NotFound(format!("Node {parent_id} is deleted")));
+        }
+        if parent.tree_id != node.tree_id {
+            return Err(AppError::InvalidInput(format!(
+                "Node {parent_id} belongs to a different tree"
+            )));
+        }
+
+        // The new parent must not be one of the moved nodes or a descendant of one
+        let mut stmt = conn.prepare(
+            "WITH RECURSIVE ancestors AS (
+                SELECT id, parent_id FROM nodes WHERE id = ?1
+                UNION ALL
+                SELECT n.id, n.parent_id
+                FROM nodes n
+                INNER JOIN ancestors a ON n.id = a.parent_id
+            )
+            SELECT id FROM ancestors",
+        )?;
+        let ancestor_ids = stmt
+            .query_map([parent_id], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+        if ancestor_ids.iter().any(|id| moved_ids.contains(id)) {
+            return Err(AppError::InvalidInput(format!(
+                "Cannot move nodes under {parent_id}: it is inside the moved branch"
+            )));
+        }
+    }
+
+    let tx = conn.transaction()?;
+    for id in &moved_ids {
+        tx.execute(
+            "UPDATE nodes SET parent_id = ?1, updated_at = datetime('now') WHERE id = ?2",
+            (&new_parent_id, id),
+        )?;
+    }
+    tx.commit()?;
+
+    moved_ids
+        .iter()
+        .map(|id| get_node_by_id(&conn, id))
+        .collect()
+}
+
+/// Soft delete a node (move to trash), optionally recording why
+#[tauri::command]
+pub fn delete_node(
+    state: State<Arc<AppState>>,
+    id: String,
+    reason_id: Option<String>,
+) -> Result<Node> {
+    let conn = state.db.conn();
+
+    if let Some(ref reason_id) = reason_id {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM delete_reasons WHERE id = ?1)",
+            [reason_id],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Err(AppError::InvalidInput(format!(
+                "Unknown delete reason '{reason_id}'"
+            )));
+        }
+    }
+
+    let rows_affected = conn.execute(
+        "UPDATE nodes SET deleted_at = datetime('now'), updated_at = datetime('now'), delete_reason_id = ?2 WHERE id = ?1 AND deleted_at IS NULL",
+        (&id, &reason_id),
     )?;
 
     if rows_affected == 0 {
@@ -228,7 +1022,7 @@ pub fn restore_node(state: State<Arc<AppState>>, id: String) -> Result<Node> {
     let conn = state.db.conn();
 
     let rows_affected = conn.execute(
-        "UPDATE nodes SET deleted_at = NULL, updated_at = datetime('now') WHERE id = ?1 AND deleted_at IS NOT NULL",
+        "UPDATE nodes SET deleted_at = NULL, delete_reason_id = NULL, updated_at = datetime('now') WHERE id = ?1 AND deleted_at IS NOT NULL",
         (&id,),
     )?;
 
@@ -239,6 +1033,49 @@ pub fn restore_node(state: State<Arc<AppState>>, id: String) -> Result<Node> {
     get_node_by_id(&conn, &id)
 }
 
+/// List deleted nodes (trash), optionally filtered by delete reason
+#[tauri::command]
+pub fn list_deleted_nodes(
+    state: State<Arc<AppState>>,
+    reason_id: Option<String>,
+) -> Result<Vec<Node>> {
+    let conn = state.db.conn();
+
+    let mut stmt = conn.prepare(
+        "SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed, delete_reason_id, params, content_hash
+         FROM nodes
+         WHERE deleted_at IS NOT NULL AND (?1 IS NULL OR delete_reason_id = ?1)
+         ORDER BY deleted_at DESC",
+    )?;
+
+    let nodes = stmt
+        .query_map([&reason_id], map_node)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(nodes)
+}
+
+/// List the preset reasons available when deleting a node
+#[tauri::command]
+pub fn list_delete_reasons(state: State<Arc<AppState>>) -> Result<Vec<DeleteReason>> {
+    let conn = state.db.conn();
+
+    let mut stmt =
+        conn.prepare("SELECT id, label, created_at FROM delete_reasons ORDER BY label ASC")?;
+
+    let reasons = stmt
+        .query_map([], |row| {
+            Ok(DeleteReason {
+                id: row.get(0)?,
+                label: row.get(1)?,
+                created_at: row.get(2)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(reasons)
+}
+
 /// Permanently delete a node (cannot be undone)
 /// Note: Due to CASCADE, this will also delete all child nodes
 #[tauri::command]
@@ -254,8 +1091,128 @@ pub fn permanently_delete_node(state: State<Arc<AppState>>, id: String) -> Resul
     Ok(())
 }
 
+/// Permanently delete a trashed node and its trashed descendants
+/// Active descendants are moved up to the purged node's parent instead of being removed.
+#[tauri::command]
+pub fn purge_node_subtree(state: State<Arc<AppState>>, node_id: String) -> Result<PurgeReport> {
+    let mut conn = state.db.conn();
+    let tx = conn.transaction()?;
+
+    let parent_id: Option<String> = tx
+        .query_row(
+            "SELECT parent_id FROM nodes WHERE id = ?1 AND deleted_at IS NOT NULL",
+            [&node_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => {
+                AppError::NotFound(format!("Deleted node {node_id} not found"))
+            }
+            _ => AppError::Database(e),
+        })?;
+
+    let ids = {
+        let mut stmt = tx.prepare(
+            "WITH RECURSIVE trashed AS (
+                SELECT id FROM nodes WHERE id = ?1
+                UNION ALL
+                SELECT n.id FROM nodes n
+                INNER JOIN trashed t ON n.parent_id = t.id
+                WHERE n.deleted_at IS NOT NULL
+            )
+            SELECT id FROM trashed",
+        )?;
+        let ids = stmt
+            .query_map([&node_id], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+        ids
+    };
+
+    let mut reparented = 0;
+    for id in &ids {
+        reparented += tx.execute(
+            "UPDATE nodes SET parent_id = ?1, updated_at = datetime('now') WHERE parent_id = ?2 AND deleted_at IS NULL",
+            (&parent_id, id),
+        )?;
+    }
+
+    let mut purged = 0;
+    for id in ids.iter().rev() {
+        purged += tx.execute("DELETE FROM nodes WHERE id = ?1", [id])?;
+    }
+
+    tx.commit()?;
+
+    Ok(PurgeReport { purged, reparented })
+}
+
+/// Helper function to get the active path from the root to a node
+fn query_node_path(
+    conn: &std::sync::MutexGuard<'_, rusqlite::Connection>,
+    node_id: &str,
+) -> Result<Vec<Node>> {
+    // Use recursive CTE to traverse up the tree
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE path AS (
+            SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed, delete_reason_id, params, content_hash, 0 as depth
+            FROM nodes
+            WHERE id = ?1 AND deleted_at IS NULL
+            UNION ALL
+            SELECT n.id, n.tree_id, n.parent_id, n.user_content, n.assistant_content, n.summary, n.model, n.tokens, n.created_at, n.updated_at, n.deleted_at, n.failed, n.delete_reason_id, n.params, n.content_hash, p.depth + 1
+            FROM nodes n
+            INNER JOIN path p ON n.id = p.parent_id
+            WHERE n.deleted_at IS NULL
+        )
+        SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed, delete_reason_id, params, content_hash
+        FROM path
+        ORDER BY depth DESC",
+    )?;
+
+    let nodes = stmt
+        .query_map([node_id], map_node)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    if nodes.is_empty() {
+        return Err(AppError::NotFound(format!("Node {node_id} not found")));
+    }
+
+    Ok(nodes)
+}
+
+/// Helper function to shorten content to at most `max_chars` characters
+fn snippet(content: &str, max_chars: usize) -> String {
+    let content = content.trim();
+    match content.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", content[..end].trim_end()),
+        None => content.to_string(),
+    }
+}
+
+/// Helper function to recompute a node's stored content hash from its current content
+fn refresh_content_hash(conn: &rusqlite::Connection, id: &str) -> Result<()> {
+    let (user_content, assistant_content): (String, Option<String>) = conn.query_row(
+        "SELECT user_content, assistant_content FROM nodes WHERE id = ?1",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    conn.execute(
+        "UPDATE nodes SET content_hash = ?1 WHERE id = ?2",
+        (
+            content_hash(&user_content, assistant_content.as_deref()),
+            id,
+        ),
+    )?;
+    Ok(())
+}
+
+/// Helper function to serialize generation parameters for storage
+pub(crate) fn params_to_json(params: &GenerationParams) -> Result<String> {
+    serde_json::to_string(params)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid generation parameters: {e}")))
+}
+
 /// Helper function to map a row to a Node
-fn map_node(row: &rusqlite::Row<'_>) -> rusqlite::Result<Node> {
+pub(crate) fn map_node(row: &rusqlite::Row<'_>) -> rusqlite::Result<Node> {
     Ok(Node {
         id: row.get(0)?,
         tree_id: row.get(1)?,
@@ -269,6 +1226,13 @@ fn map_node(row: &rusqlite::Row<'_>) -> rusqlite::Result<Node> {
         updated_at: row.get(9)?,
         deleted_at: row.get(10)?,
         failed: row.get::<_, i32>(11)? != 0,
+        delete_reason_id: row.get(12)?,
+        // Params written outside the app may not fit the struct; treat those as unrecorded
+        // rather than failing every query that touches the node
+        params: row
+            .get::<_, Option<String>>(13)?
+            .and_then(|params| serde_json::from_str(&params).ok()),
+        content_hash: row.get(14)?,
     })
 }
 
@@ -278,7 +1242,7 @@ fn get_node_by_id(
     id: &str,
 ) -> Result<Node> {
     conn.query_row(
-        "SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed
+        "SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed, delete_reason_id, params, content_hash
          FROM nodes WHERE id = ?1",
         [id],
         map_node,
@@ -288,3 +1252,871 @@ fn get_node_by_id(
         _ => AppError::Database(e),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::{new_node, TempPath, TestApp};
+
+    #[test]
+    fn update_node_applies_patches() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let node = create_node(
+            app.state(),
+            CreateNode {
+                assistant_content: Some("answer".to_string()),
+                summary: Some("summary".to_string()),
+                model: Some("old".to_string()),
+                tokens: Some(3),
+                ..new_node(&tree.id, None, "question")
+            },
+        )
+        .unwrap();
+
+        let input = serde_json::from_str(r#"{"summary": null, "model": "new"}"#).unwrap();
+        let updated = update_node(app.state(), node.id, input).unwrap();
+
+        assert_eq!(updated.summary, None);
+        assert_eq!(updated.model.as_deref(), Some("new"));
+        assert_eq!(updated.assistant_content.as_deref(), Some("answer"));
+        assert_eq!(updated.tokens, Some(3));
+    }
+
+    #[test]
+    fn branch_map_groups_active_children_in_order() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let root = app.node(&tree.id, None, "root");
+        let first = app.node(&tree.id, Some(&root.id), "first");
+        let second = app.node(&tree.id, Some(&root.id), "second");
+        let trashed = app.node(&tree.id, None, "trashed");
+        delete_node(app.state(), trashed.id, None).unwrap();
+
+        let branches = get_branch_map(app.state(), tree.id).unwrap();
+
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[BRANCH_MAP_ROOT_KEY], vec![root.id.clone()]);
+        assert_eq!(branches[&root.id], vec![first.id, second.id]);
+    }
+
+    #[test]
+    fn token_distribution_shares_sum_to_one() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        for tokens in [Some(30), Some(10), None] {
+            let input = CreateNode {
+                tokens,
+                ..new_node(&tree.id, None, "turn")
+            };
+            create_node(app.state(), input).unwrap();
+        }
+
+        let shares = get_token_distribution(app.state(), tree.id).unwrap();
+
+        let fractions: Vec<f64> = shares.iter().map(|share| share.fraction).collect();
+        assert_eq!(
+            shares.iter().map(|share| share.tokens).collect::<Vec<_>>(),
+            [30, 10, 0]
+        );
+        assert!((fractions[0] - 0.75).abs() < 1e-9);
+        assert!((fractions[1] - 0.25).abs() < 1e-9);
+        assert!(fractions[2].abs() < 1e-9);
+    }
+
+    #[test]
+    fn token_distribution_without_tokens_is_all_zero() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        app.node(&tree.id, None, "turn");
+
+        let shares = get_token_distribution(app.state(), tree.id).unwrap();
+
+        assert_eq!(shares.len(), 1);
+        assert!(shares[0].fraction.abs() < 1e-9);
+    }
+
+    #[test]
+    fn list_nodes_for_trees_groups_by_tree() {
+        let app = TestApp::new();
+        let first = app.tree(None, "First");
+        let second = app.tree(None, "Second");
+        app.node(&first.id, None, "a");
+        app.node(&second.id, None, "b");
+        app.node(&first.id, None, "c");
+
+        let tree_ids = vec![second.id.clone(), first.id.clone(), first.id.clone()];
+        let nodes = list_nodes_for_trees(app.state(), tree_ids).unwrap();
+
+        let order: Vec<&str> = nodes.iter().map(|node| node.tree_id.as_str()).collect();
+        let mut sorted = order.clone();
+        sorted.sort_unstable();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(order, sorted);
+        assert!(list_nodes_for_trees(app.state(), Vec::new())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn list_nodes_for_trees_spans_chunks() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        app.node(&tree.id, None, "turn");
+        let mut tree_ids: Vec<String> = (0..TREE_ID_CHUNK_SIZE)
+            .map(|i| format!("missing-{i}"))
+            .collect();
+        tree_ids.push(tree.id);
+
+        assert_eq!(
+            list_nodes_for_trees(app.state(), tree_ids).unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn trashed_nodes_filter_by_delete_reason() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let duplicate = app.node(&tree.id, None, "a");
+        let irrelevant = app.node(&tree.id, None, "b");
+        let unexplained = app.node(&tree.id, None, "c");
+
+        delete_node(
+            app.state(),
+            duplicate.id.clone(),
+            Some("duplicate".to_string()),
+        )
+        .unwrap();
+        delete_node(app.state(), irrelevant.id, Some("irrelevant".to_string())).unwrap();
+        assert!(delete_node(
+            app.state(),
+            unexplained.id.clone(),
+            Some("unknown".to_string())
+        )
+        .is_err());
+        delete_node(app.state(), unexplained.id, None).unwrap();
+
+        let duplicates = list_deleted_nodes(app.state(), Some("duplicate".to_string())).unwrap();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].id, duplicate.id);
+        assert_eq!(duplicates[0].delete_reason_id.as_deref(), Some("duplicate"));
+        assert_eq!(list_deleted_nodes(app.state(), None).unwrap().len(), 3);
+        assert_eq!(list_delete_reasons(app.state()).unwrap().len(), 3);
+
+        let restored = restore_node(app.state(), duplicate.id).unwrap();
+        assert_eq!(restored.delete_reason_id, None);
+    }
+
+    #[test]
+    fn move_siblings_from_moves_later_siblings() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let root = app.node(&tree.id, None, "root");
+        let first = app.node(&tree.id, Some(&root.id), "first");
+        let second = app.node(&tree.id, Some(&root.id), "second");
+        let third = app.node(&tree.id, Some(&root.id), "third");
+        let grandchild = app.node(&tree.id, Some(&second.id), "grandchild");
+
+        // The new parent can't be one of the moved nodes or under one of them
+        assert!(move_siblings_from(app.state(), second.id.clone(), Some(grandchild.id)).is_err());
+        assert!(
+            move_siblings_from(app.state(), second.id.clone(), Some(third.id.clone())).is_err()
+        );
+
+        let moved =
+            move_siblings_from(app.state(), second.id.clone(), Some(first.id.clone())).unwrap();
+        let moved_ids: Vec<&str> = moved.iter().map(|node| node.id.as_str()).collect();
+        assert_eq!(moved_ids, [second.id.as_str(), third.id.as_str()]);
+        assert!(moved
+            .iter()
+            .all(|node| node.parent_id.as_deref() == Some(first.id.as_str())));
+
+        let moved = move_siblings_from(app.state(), second.id, None).unwrap();
+        assert_eq!(moved.len(), 2);
+        assert!(moved.iter().all(|node| node.parent_id.is_none()));
+    }
+
+    #[test]
+    fn mark_nodes_failed_counts_newly_failed_nodes_once() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let nodes: Vec<Node> = (0..4)
+            .map(|i| app.node(&tree.id, None, &format!("turn {i}")))
+            .collect();
+        delete_node(app.state(), nodes[3].id.clone(), None).unwrap();
+
+        let mut ids: Vec<String> = nodes.iter().map(|node| node.id.clone()).collect();
+        ids.push("missing".to_string());
+        ids.push(nodes[0].id.clone());
+        let marked = mark_nodes_failed(app.state(), ids).unwrap();
+
+        assert_eq!(marked, 3);
+        assert_eq!(
+            mark_nodes_failed(app.state(), vec![nodes[0].id.clone()]).unwrap(),
+            0
+        );
+        let listed = list_nodes(app.state(), tree.id).unwrap();
+        assert_eq!(listed.iter().filter(|node| node.failed).count(), 3);
+    }
+
+    #[test]
+    fn new_nodes_take_the_tree_default_model() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        app.execute(
+            "UPDATE trees SET default_model = 'gpt' WHERE id = ?1",
+            [&tree.id],
+        );
+
+        let defaulted = app.node(&tree.id, None, "first");
+        let explicit = create_node(
+            app.state(),
+            CreateNode {
+                model: Some("claude".to_string()),
+                ..new_node(&tree.id, None, "second")
+            },
+        )
+        .unwrap();
+
+        assert_eq!(defaulted.model.as_deref(), Some("gpt"));
+        assert_eq!(explicit.model.as_deref(), Some("claude"));
+    }
+
+    #[test]
+    fn list_nodes_by_model_matches_case_insensitively() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        for model in ["GPT-4", "claude", "gpt-4"] {
+            let input = CreateNode {
+                model: Some(model.to_string()),
+                ..new_node(&tree.id, None, "turn")
+            };
+            create_node(app.state(), input).unwrap();
+        }
+
+        let matches = list_nodes_by_model(app.state(), "gpt-4".to_string(), 10).unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].tree_name, "Tree");
+        assert_eq!(
+            list_nodes_by_model(app.state(), "gpt-4".to_string(), 1)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // The node's fields are flattened next to the tree name
+        let json = serde_json::to_value(&matches[0]).unwrap();
+        assert!(json.get("tree_name").is_some());
+        assert!(json.get("user_content").is_some());
+    }
+
+    #[test]
+    fn normalize_whitespace_only_touches_untidy_nodes() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let untidy_user = app.node(&tree.id, None, "hi  \nthere\t");
+        let untidy_answer = create_node(
+            app.state(),
+            CreateNode {
+                assistant_content: Some("a\n\n\n\nb\n\n".to_string()),
+                ..new_node(&tree.id, None, "ok")
+            },
+        )
+        .unwrap();
+        let tidy = app.node(&tree.id, None, "clean\n\nfine");
+
+        assert_eq!(
+            normalize_whitespace(app.state(), tree.id.clone()).unwrap(),
+            2
+        );
+        assert_eq!(
+            get_node(app.state(), untidy_user.id).unwrap().user_content,
+            "hi\nthere"
+        );
+        assert_eq!(
+            get_node(app.state(), untidy_answer.id)
+                .unwrap()
+                .assistant_content
+                .as_deref(),
+            Some("a\n\nb")
+        );
+        assert!(get_node(app.state(), tidy.id).unwrap().updated_at.is_none());
+        assert_eq!(normalize_whitespace(app.state(), tree.id).unwrap(), 0);
+    }
+
+    #[test]
+    fn longest_branch_follows_the_deepest_leaf() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        assert!(get_longest_branch(app.state(), tree.id.clone())
+            .unwrap()
+            .is_empty());
+
+        let root = app.node(&tree.id, None, "root");
+        app.node(&tree.id, Some(&root.id), "short");
+        let long = app.node(&tree.id, Some(&root.id), "long");
+        let leaf = app.node(&tree.id, Some(&long.id), "leaf");
+
+        let branch = get_longest_branch(app.state(), tree.id).unwrap();
+        let ids: Vec<&str> = branch.iter().map(|node| node.id.as_str()).collect();
+        assert_eq!(ids, [root.id.as_str(), long.id.as_str(), leaf.id.as_str()]);
+    }
+
+    /// Helper function to create a turn with an assistant reply
+    fn answered_node(
+        app: &TestApp,
+        tree_id: &str,
+        parent_id: Option<&str>,
+        user: &str,
+        assistant: &str,
+    ) -> Node {
+        let input = CreateNode {
+            assistant_content: Some(assistant.to_string()),
+            ..new_node(tree_id, parent_id, user)
+        };
+        create_node(app.state(), input).unwrap()
+    }
+
+    #[test]
+    fn render_path_text_skips_failed_turns_and_blank_replies() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let root = answered_node(&app, &tree.id, None, "Hi", "Hello!");
+        let failed = app.node(&tree.id, Some(&root.id), "bad");
+        mark_nodes_failed(app.state(), vec![failed.id.clone()]).unwrap();
+        let leaf = answered_node(&app, &tree.id, Some(&failed.id), "Bye", " ");
+
+        assert_eq!(
+            render_path_text(app.state(), leaf.id, None).unwrap(),
+            "User: Hi\n\nAssistant: Hello!\n\nUser: Bye"
+        );
+        assert_eq!(
+            render_path_text(app.state(), root.id, Some("\n---\n".to_string())).unwrap(),
+            "User: Hi\n---\nAssistant: Hello!"
+        );
+    }
+
+    #[test]
+    fn branch_alternatives_preview_each_sibling() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let root = app.node(&tree.id, None, "root");
+        let current = app.node(&tree.id, Some(&root.id), "current");
+        let busy = app.node(&tree.id, Some(&root.id), "busy");
+        app.node(&tree.id, Some(&root.id), "quiet");
+        let follow_up = app.node(&tree.id, Some(&busy.id), "follow up");
+        app.node(&tree.id, Some(&follow_up.id), "deeper");
+
+        let alternatives = get_branch_alternatives(app.state(), current.id).unwrap();
+
+        assert_eq!(alternatives.len(), 2);
+        assert_eq!(alternatives[0].sibling_node.id, busy.id);
+        assert_eq!(alternatives[0].descendant_count, 2);
+        assert_eq!(
+            alternatives[0].first_child_preview.as_deref(),
+            Some("follow up")
+        );
+        assert_eq!(alternatives[1].descendant_count, 0);
+        assert_eq!(alternatives[1].first_child_preview, None);
+    }
+
+    #[test]
+    fn branch_alternatives_of_a_root_are_other_roots() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let first = app.node(&tree.id, None, "first");
+        let second = app.node(&tree.id, None, "second");
+
+        let alternatives = get_branch_alternatives(app.state(), second.id).unwrap();
+
+        assert_eq!(alternatives.len(), 1);
+        assert_eq!(alternatives[0].sibling_node.id, first.id);
+    }
+
+    #[test]
+    fn reading_time_counts_words_on_the_active_path() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let root = answered_node(
+            &app,
+            &tree.id,
+            None,
+            "one two three",
+            "four five\nsix seven",
+        );
+        let failed = app.node(&tree.id, Some(&root.id), "x y z");
+        mark_nodes_failed(app.state(), vec![failed.id.clone()]).unwrap();
+        let leaf = app.node(&tree.id, Some(&failed.id), "eight");
+
+        let reading = get_path_reading_time(app.state(), leaf.id.clone(), Some(4)).unwrap();
+        assert_eq!(reading.word_count, 8);
+        assert!((reading.minutes - 2.0).abs() < 1e-9);
+
+        let default = get_path_reading_time(app.state(), leaf.id.clone(), None).unwrap();
+        assert!((default.minutes - 8.0 / f64::from(DEFAULT_WORDS_PER_MINUTE)).abs() < 1e-9);
+        assert!(matches!(
+            get_path_reading_time(app.state(), leaf.id, Some(0)),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn generation_params_round_trip_through_storage() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let params = GenerationParams {
+            temperature: Some(0.7),
+            top_p: None,
+            max_tokens: Some(512),
+            seed: Some(42),
+        };
+        let node = create_node(
+            app.state(),
+            CreateNode {
+                params: Some(params.clone()),
+                ..new_node(&tree.id, None, "turn")
+            },
+        )
+        .unwrap();
+
+        assert_eq!(node.params.as_ref(), Some(&params));
+        assert_eq!(
+            get_node_params(app.state(), node.id.clone()).unwrap(),
+            Some(params)
+        );
+        let stored: String = app
+            .state()
+            .db
+            .conn()
+            .query_row(
+                "SELECT params FROM nodes WHERE id = ?1",
+                [&node.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(stored, r#"{"temperature":0.7,"max_tokens":512,"seed":42}"#);
+
+        let cleared = set_node_params(app.state(), node.id, None).unwrap();
+        assert_eq!(cleared.params, None);
+    }
+
+    #[test]
+    fn malformed_stored_params_read_as_unset() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let node = app.node(&tree.id, None, "turn");
+
+        // The column only accepts JSON objects
+        let state = app.state();
+        let array = state
+            .db
+            .conn()
+            .execute("UPDATE nodes SET params = '[1]' WHERE id = ?1", [&node.id]);
+        assert!(array.is_err());
+        app.execute(
+            r#"UPDATE nodes SET params = '{"temperature": "hot"}' WHERE id = ?1"#,
+            [&node.id],
+        );
+
+        assert_eq!(get_node(app.state(), node.id).unwrap().params, None);
+        assert_eq!(list_nodes(app.state(), tree.id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn ancestor_ids_run_from_root_to_node() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let root = app.node(&tree.id, None, "root");
+        let child = app.node(&tree.id, Some(&root.id), "child");
+        let grandchild = app.node(&tree.id, Some(&child.id), "grandchild");
+        app.node(&tree.id, Some(&child.id), "sibling");
+
+        assert_eq!(
+            get_ancestor_ids(app.state(), grandchild.id.clone()).unwrap(),
+            [root.id.clone(), child.id, grandchild.id]
+        );
+        assert_eq!(
+            get_ancestor_ids(app.state(), root.id.clone()).unwrap(),
+            [root.id]
+        );
+        assert!(matches!(
+            get_ancestor_ids(app.state(), "missing".to_string()),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn variant_repeats_the_prompt_without_the_reply() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        app.execute(
+            "UPDATE trees SET default_model = 'gpt' WHERE id = ?1",
+            [&tree.id],
+        );
+        let root = app.node(&tree.id, None, "root");
+        let original = create_node(
+            app.state(),
+            CreateNode {
+                assistant_content: Some("answer".to_string()),
+                summary: Some("summary".to_string()),
+                model: Some("claude".to_string()),
+                tokens: Some(5),
+                ..new_node(&tree.id, Some(&root.id), "ask")
+            },
+        )
+        .unwrap();
+
+        let variant = create_variant(app.state(), original.id.clone()).unwrap();
+
+        assert_ne!(variant.id, original.id);
+        assert_eq!(variant.parent_id, original.parent_id);
+        assert_eq!(variant.tree_id, tree.id);
+        assert_eq!(variant.user_content, "ask");
+        assert_eq!(variant.assistant_content, None);
+        assert_eq!(variant.summary, None);
+        assert_eq!(variant.tokens, None);
+        assert_eq!(variant.model.as_deref(), Some("gpt"));
+        assert_eq!(
+            get_node(app.state(), original.id)
+                .unwrap()
+                .assistant_content
+                .as_deref(),
+            Some("answer")
+        );
+
+        assert_eq!(
+            create_variant(app.state(), root.id).unwrap().parent_id,
+            None
+        );
+        assert!(matches!(
+            create_variant(app.state(), "missing".to_string()),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn purge_node_subtree_keeps_active_descendants() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let root = app.node(&tree.id, None, "root");
+        let trashed = app.node(&tree.id, Some(&root.id), "trashed");
+        let trashed_child = app.node(&tree.id, Some(&trashed.id), "trashed child");
+        let active = app.node(&tree.id, Some(&trashed_child.id), "active");
+        let under_active = app.node(&tree.id, Some(&active.id), "under active");
+        let unrelated = app.node(&tree.id, Some(&root.id), "unrelated");
+        for node in [&trashed, &trashed_child, &under_active, &unrelated] {
+            delete_node(app.state(), node.id.clone(), None).unwrap();
+        }
+
+        // Only trashed nodes can be purged
+        assert!(matches!(
+            purge_node_subtree(app.state(), root.id.clone()),
+            Err(AppError::NotFound(_))
+        ));
+
+        let report = purge_node_subtree(app.state(), trashed.id.clone()).unwrap();
+
+        assert_eq!((report.purged, report.reparented), (2, 1));
+        assert!(get_node(app.state(), trashed.id).is_err());
+        assert!(get_node(app.state(), trashed_child.id).is_err());
+        assert_eq!(
+            get_node(app.state(), active.id).unwrap().parent_id,
+            Some(root.id)
+        );
+        assert!(get_node(app.state(), under_active.id)
+            .unwrap()
+            .deleted_at
+            .is_some());
+        assert!(get_node(app.state(), unrelated.id)
+            .unwrap()
+            .deleted_at
+            .is_some());
+    }
+
+    #[test]
+    fn count_branches_counts_active_leaves() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        assert_eq!(count_branches(app.state(), tree.id.clone()).unwrap(), 0);
+
+        let root = app.node(&tree.id, None, "root");
+        let forked = app.node(&tree.id, Some(&root.id), "forked");
+        app.node(&tree.id, Some(&forked.id), "left");
+        app.node(&tree.id, Some(&forked.id), "right");
+        let straight = app.node(&tree.id, Some(&root.id), "straight");
+        app.node(&tree.id, None, "second root");
+        assert_eq!(count_branches(app.state(), tree.id.clone()).unwrap(), 4);
+
+        // Extending a leaf doesn't add a branch, and trashed leaves don't count
+        let extension = app.node(&tree.id, Some(&straight.id), "extension");
+        assert_eq!(count_branches(app.state(), tree.id.clone()).unwrap(), 4);
+        delete_node(app.state(), extension.id, None).unwrap();
+        assert_eq!(count_branches(app.state(), tree.id).unwrap(), 4);
+    }
+
+    #[test]
+    fn node_context_has_active_parent_and_children() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let root = app.node(&tree.id, None, "root");
+        let middle = app.node(&tree.id, Some(&root.id), "middle");
+        let child = app.node(&tree.id, Some(&middle.id), "child");
+        let trashed = app.node(&tree.id, Some(&middle.id), "trashed");
+        delete_node(app.state(), trashed.id, None).unwrap();
+
+        let context = get_node_context(app.state(), middle.id.clone()).unwrap();
+        assert_eq!(context.node.id, middle.id);
+        assert_eq!(
+            context.parent.map(|parent| parent.id),
+            Some(root.id.clone())
+        );
+        let children: Vec<String> = context.children.into_iter().map(|node| node.id).collect();
+        assert_eq!(children, [child.id]);
+
+        let root_context = get_node_context(app.state(), root.id.clone()).unwrap();
+        assert!(root_context.parent.is_none());
+        assert_eq!(root_context.children.len(), 1);
+
+        delete_node(app.state(), root.id, None).unwrap();
+        assert!(get_node_context(app.state(), middle.id)
+            .unwrap()
+            .parent
+            .is_none());
+        assert!(matches!(
+            get_node_context(app.state(), "missing".to_string()),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    /// Helper function to create a root turn with tokens, backdated by whole days
+    fn node_days_ago(app: &TestApp, tree_id: &str, tokens: i32, days: u32) {
+        let node = create_node(
+            app.state(),
+            CreateNode {
+                tokens: Some(tokens),
+                ..new_node(tree_id, None, "turn")
+            },
+        )
+        .unwrap();
+        app.execute(
+            "UPDATE nodes SET created_at = datetime('now', ?1) WHERE id = ?2",
+            (format!("-{days} days"), &node.id),
+        );
+    }
+
+    #[test]
+    fn daily_token_usage_fills_quiet_days_with_zero() {
+        let app = TestApp::new();
+        let project = app.project("Project");
+        let tree = app.tree(Some(&project.id), "Tree");
+        let elsewhere = app.tree(None, "Elsewhere");
+        node_days_ago(&app, &tree.id, 10, 0);
+        node_days_ago(&app, &tree.id, 5, 0);
+        node_days_ago(&app, &tree.id, 7, 3);
+        node_days_ago(&app, &elsewhere.id, 100, 0);
+        node_days_ago(&app, &tree.id, 1000, 10);
+
+        let usage = get_daily_token_usage(app.state(), Some(project.id), 5).unwrap();
+
+        let totals: Vec<(i64, i64)> = usage
+            .iter()
+            .map(|day| (day.total_tokens, day.node_count))
+            .collect();
+        assert_eq!(totals, [(0, 0), (7, 1), (0, 0), (0, 0), (15, 2)]);
+        assert!(usage.windows(2).all(|days| days[0].date < days[1].date));
+
+        let today = get_daily_token_usage(app.state(), None, 1).unwrap();
+        assert_eq!(today.len(), 1);
+        assert_eq!(today[0].total_tokens, 115);
+    }
+
+    #[test]
+    fn daily_token_usage_span_is_bounded() {
+        let app = TestApp::new();
+
+        assert!(get_daily_token_usage(app.state(), None, 0)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            get_daily_token_usage(app.state(), None, MAX_USAGE_DAYS)
+                .unwrap()
+                .len(),
+            3660
+        );
+        assert!(matches!(
+            get_daily_token_usage(app.state(), None, MAX_USAGE_DAYS + 1),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn resume_candidates_are_open_leaves_deepest_first() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let root = answered_node(&app, &tree.id, None, "q", "a");
+        answered_node(&app, &tree.id, Some(&root.id), "q", "done");
+        let middle = answered_node(&app, &tree.id, Some(&root.id), "q", "a");
+        let lower = answered_node(&app, &tree.id, Some(&middle.id), "q", "a");
+        let deep = app.node(&tree.id, Some(&lower.id), "open");
+        let shallow = app.node(&tree.id, Some(&middle.id), "open");
+        let failed = app.node(&tree.id, Some(&lower.id), "failed");
+        mark_nodes_failed(app.state(), vec![failed.id]).unwrap();
+        let blank = answered_node(&app, &tree.id, Some(&root.id), "q", "  ");
+        delete_node(app.state(), blank.id, None).unwrap();
+
+        let ids: Vec<String> = list_resume_candidates(app.state(), tree.id)
+            .unwrap()
+            .into_iter()
+            .map(|node| node.id)
+            .collect();
+        assert_eq!(ids, [deep.id, shallow.id]);
+    }
+
+    #[test]
+    fn create_node_with_a_used_idempotency_key_returns_the_first_node() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let keyed = |key: Option<&str>, content: &str| CreateNode {
+            idempotency_key: key.map(ToString::to_string),
+            ..new_node(&tree.id, None, content)
+        };
+
+        let first = create_node(app.state(), keyed(Some("retry"), "first")).unwrap();
+        let retried = create_node(app.state(), keyed(Some("retry"), "second")).unwrap();
+        assert_eq!(retried.id, first.id);
+        assert_eq!(retried.user_content, "first");
+
+        let other = create_node(app.state(), keyed(Some("other"), "first")).unwrap();
+        assert_ne!(other.id, first.id);
+        let plain = create_node(app.state(), keyed(None, "plain")).unwrap();
+        let again = create_node(app.state(), keyed(None, "plain")).unwrap();
+        assert_ne!(plain.id, again.id);
+        assert_eq!(list_nodes(app.state(), tree.id.clone()).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn create_node_rejects_a_reused_idempotency_key_it_cannot_honor() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let elsewhere = app.tree(None, "Elsewhere");
+        let keyed = |tree_id: &str, parent_id: Option<&str>, key: &str| CreateNode {
+            idempotency_key: Some(key.to_string()),
+            ..new_node(tree_id, parent_id, "turn")
+        };
+
+        let root = create_node(app.state(), keyed(&tree.id, None, "root")).unwrap();
+        for retry in [
+            keyed(&elsewhere.id, None, "root"),
+            keyed(&tree.id, Some(&root.id), "root"),
+        ] {
+            assert!(matches!(
+                create_node(app.state(), retry),
+                Err(AppError::InvalidInput(_))
+            ));
+        }
+
+        let trashed = create_node(app.state(), keyed(&tree.id, None, "trashed")).unwrap();
+        delete_node(app.state(), trashed.id, None).unwrap();
+        assert!(matches!(
+            create_node(app.state(), keyed(&tree.id, None, "trashed")),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert_eq!(list_nodes(app.state(), elsewhere.id).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn tree_adjacency_indexes_edges_into_ids() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let root = app.node(&tree.id, None, "root");
+        let left = app.node(&tree.id, Some(&root.id), "left");
+        let right = app.node(&tree.id, Some(&root.id), "right");
+        let below = app.node(&tree.id, Some(&right.id), "below");
+        let second_root = app.node(&tree.id, None, "second root");
+        let deleted = app.node(&tree.id, Some(&left.id), "deleted");
+        let orphan = app.node(&tree.id, Some(&deleted.id), "orphan");
+        delete_node(app.state(), deleted.id, None).unwrap();
+
+        let adjacency = get_tree_adjacency(app.state(), tree.id).unwrap();
+
+        assert_eq!(
+            adjacency.ids,
+            [
+                root.id,
+                left.id,
+                right.id,
+                below.id,
+                second_root.id,
+                orphan.id
+            ]
+        );
+        assert_eq!(adjacency.edges, [(0, 1), (0, 2), (2, 3)]);
+        let json = serde_json::to_value(&adjacency).unwrap();
+        assert_eq!(json["edges"][0], serde_json::json!([0, 1]));
+    }
+
+    #[test]
+    fn content_hash_ignores_trailing_whitespace_and_blank_runs() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let first = answered_node(&app, &tree.id, None, "Hello  \n\n\nworld", "hi");
+        let same = answered_node(&app, &tree.id, None, "Hello\n\nworld  ", "hi\n");
+        let unanswered = app.node(&tree.id, None, "Hello\n\nworld");
+
+        let hash = first.content_hash.clone().unwrap();
+        assert_eq!(same.content_hash.as_ref(), Some(&hash));
+        assert_ne!(unanswered.content_hash.as_ref(), Some(&hash));
+
+        let found = find_node_by_hash(app.state(), hash.clone()).unwrap();
+        assert_eq!(found.unwrap().id, first.id);
+        delete_node(app.state(), first.id, None).unwrap();
+        let found = find_node_by_hash(app.state(), hash.clone()).unwrap();
+        assert_eq!(found.unwrap().id, same.id);
+        assert!(find_node_by_hash(app.state(), "0".repeat(16))
+            .unwrap()
+            .is_none());
+
+        let patch = serde_json::from_str(r#"{"assistant_content": "hi"}"#).unwrap();
+        let answered = update_node(app.state(), unanswered.id, patch).unwrap();
+        assert_eq!(answered.content_hash, Some(hash));
+    }
+
+    #[test]
+    fn content_hash_migration_backfills_existing_nodes() {
+        let file = TempPath::new("db");
+        let old_id = {
+            let app = TestApp::open(&file);
+            let tree = app.tree(None, "Tree");
+            let node = answered_node(&app, &tree.id, None, "a", "b");
+            delete_node(app.state(), node.id.clone(), None).unwrap();
+            // Roll the database back to before the content hash migration
+            app.state()
+                .db
+                .conn()
+                .execute_batch(
+                    "DROP INDEX idx_nodes_content_hash;
+                     ALTER TABLE nodes DROP COLUMN content_hash;
+                     DELETE FROM _migrations WHERE name = '010_add_node_content_hash';",
+                )
+                .unwrap();
+            node.id
+        };
+
+        let app = TestApp::open(&file);
+        let backfilled: Option<String> = app
+            .state()
+            .db
+            .conn()
+            .query_row(
+                "SELECT content_hash FROM nodes WHERE id = ?1",
+                [&old_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let tree = app.tree(None, "Tree");
+        let fresh = answered_node(&app, &tree.id, None, "a", "b");
+        assert!(backfilled.is_some());
+        assert_eq!(backfilled, fresh.content_hash);
+    }
+}
@@ -1,10 +1,16 @@
+use super::nodes::map_node;
 use crate::error::{AppError, Result};
-use crate::models::{CreateProject, Project, UpdateProject};
+use crate::models::{CreateProject, Project, ProjectExportLine, ProjectRenamed, UpdateProject};
 use crate::AppState;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::sync::Arc;
-use tauri::State;
+use tauri::{AppHandle, Emitter, Runtime, State};
 use uuid::Uuid;
 
+/// Event emitted after a project has been renamed
+pub const PROJECT_RENAMED_EVENT: &str = "project:renamed";
+
 /// Create a new project
 #[tauri::command]
 pub fn create_project(state: State<Arc<AppState>>, input: CreateProject) -> Result<Project> {
@@ -39,15 +45,7 @@ pub fn list_projects(state: State<Arc<AppState>>) -> Result<Vec<Project>> {
     )?;
 
     let projects = stmt
-        .query_map([], |row| {
-            Ok(Project {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                created_at: row.get(2)?,
-                updated_at: row.get(3)?,
-                deleted_at: row.get(4)?,
-            })
-        })?
+        .query_map([], map_project)?
         .collect::<std::result::Result<Vec<_>, _>>()?;
 
     Ok(projects)
@@ -66,15 +64,7 @@ pub fn list_deleted_projects(state: State<Arc<AppState>>) -> Result<Vec<Project>
     )?;
 
     let projects = stmt
-        .query_map([], |row| {
-            Ok(Project {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                created_at: row.get(2)?,
-                updated_at: row.get(3)?,
-                deleted_at: row.get(4)?,
-            })
-        })?
+        .query_map([], map_project)?
         .collect::<std::result::Result<Vec<_>, _>>()?;
 
     Ok(projects)
@@ -105,6 +95,42 @@ pub fn update_project(
     get_project_by_id(&conn, &id)
 }
 
+/// Rename a project, rejecting names already used by another active project
+#[tauri::command]
+pub fn rename_project<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<Arc<AppState>>,
+    id: String,
+    name: String,
+) -> Result<Project> {
+    let conn = state.db.conn();
+
+    let existing = get_project_by_id(&conn, &id)?;
+    if existing.deleted_at.is_some() {
+        return Err(AppError::NotFound(format!("Project {id} is deleted")));
+    }
+
+    let name = validate_project_name(&conn, &name, &id)?;
+
+    conn.execute(
+        "UPDATE projects SET name = ?1, updated_at = datetime('now') WHERE id = ?2",
+        (name, &id),
+    )?;
+    let project = get_project_by_id(&conn, &id)?;
+
+    // The rename is already committed, so a failed emit is not reported as an error
+    let _ = app.emit(
+        PROJECT_RENAMED_EVENT,
+        ProjectRenamed {
+            id: project.id.clone(),
+            old_name: existing.name,
+            name: project.name.clone(),
+        },
+    );
+
+    Ok(project)
+}
+
 /// Soft delete a project (move to trash)
 #[tauri::command]
 pub fn delete_project(state: State<Arc<AppState>>, id: String) -> Result<Project> {
@@ -155,6 +181,92 @@ pub fn permanently_delete_project(state: State<Arc<AppState>>, id: String) -> Re
     Ok(())
 }
 
+/// Export every node in a project as newline-delimited JSON, returning the line count
+#[tauri::command]
+pub fn export_project_ndjson(
+    state: State<Arc<AppState>>,
+    project_id: String,
+    path: String,
+) -> Result<u64> {
+    let conn = state.db.conn();
+    let project = get_project_by_id(&conn, &project_id)?;
+    if project.deleted_at.is_some() {
+        return Err(AppError::NotFound(format!(
+            "Project {project_id} is deleted"
+        )));
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT n.id, n.tree_id, n.parent_id, n.user_content, n.assistant_content, n.summary, n.model, n.tokens, n.created_at, n.updated_at, n.deleted_at, n.failed, n.delete_reason_id, n.params, n.content_hash, t.name
+         FROM nodes n
+         INNER JOIN trees t ON t.id = n.tree_id
+         WHERE t.project_id = ?1
+           AND n.deleted_at IS NULL
+           AND t.deleted_at IS NULL
+         ORDER BY t.created_at ASC, n.created_at ASC",
+    )?;
+    let mut rows = stmt.query([&project_id])?;
+
+    let mut writer = BufWriter::new(File::create(&path)?);
+    let mut count = 0u64;
+    while let Some(row) = rows.next()? {
+        let line = ProjectExportLine {
+            project_id: project.id.clone(),
+            project_name: project.name.clone(),
+            tree_name: row.get(15)?,
+            node: map_node(row)?,
+        };
+        serde_json::to_writer(&mut writer, &line).map_err(std::io::Error::from)?;
+        writer.write_all(b"\n")?;
+        count += 1;
+    }
+    writer.flush()?;
+
+    Ok(count)
+}
+
+/// Helper function to trim a new name for project `id` and check it is non-empty and not
+/// already used (ignoring case) by another active project
+fn validate_project_name<'a>(
+    conn: &rusqlite::Connection,
+    name: &'a str,
+    id: &str,
+) -> Result<&'a str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Project name cannot be empty".to_string(),
+        ));
+    }
+
+    let taken: bool = conn.query_row(
+        "SELECT EXISTS(
+            SELECT 1 FROM projects
+            WHERE name = ?1 COLLATE NOCASE AND id != ?2 AND deleted_at IS NULL
+         )",
+        (name, id),
+        |row| row.get(0),
+    )?;
+    if taken {
+        return Err(AppError::InvalidInput(format!(
+            "A project named \"{name}\" already exists"
+        )));
+    }
+
+    Ok(name)
+}
+
+/// Helper function to map a row to a Project
+pub(crate) fn map_project(row: &rusqlite::Row<'_>) -> rusqlite::Result<Project> {
+    Ok(Project {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        created_at: row.get(2)?,
+        updated_at: row.get(3)?,
+        deleted_at: row.get(4)?,
+    })
+}
+
 /// Helper function to get a project by ID
 fn get_project_by_id(
     conn: &std::sync::MutexGuard<'_, rusqlite::Connection>,
@@ -163,15 +275,7 @@ fn get_project_by_id(
     conn.query_row(
         "SELECT id, name, created_at, updated_at, deleted_at FROM projects WHERE id = ?1",
         [id],
-        |row| {
-            Ok(Project {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                created_at: row.get(2)?,
-                updated_at: row.get(3)?,
-                deleted_at: row.get(4)?,
-            })
-        },
+        map_project,
     )
     .map_err(|e| match e {
         rusqlite::Error::QueryReturnedNoRows => {
@@ -180,3 +284,97 @@ fn get_project_by_id(
         _ => AppError::Database(e),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::{TempPath, TestApp};
+
+    #[test]
+    fn export_project_ndjson_writes_one_line_per_node() {
+        let app = TestApp::new();
+        let project = app.project("Project");
+        let tree = app.tree(Some(&project.id), "Tree");
+        let root = app.node(&tree.id, None, "root");
+        app.node(&tree.id, Some(&root.id), "child");
+        let elsewhere = app.tree(None, "Elsewhere");
+        app.node(&elsewhere.id, None, "not exported");
+        let file = TempPath::new("ndjson");
+
+        assert_eq!(
+            export_project_ndjson(app.state(), project.id.clone(), file.path()).unwrap(),
+            2
+        );
+
+        let body = file.read();
+        let lines: Vec<serde_json::Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            assert_eq!(line["project_name"], "Project");
+            assert_eq!(line["tree_id"], tree.id.as_str());
+        }
+        assert!(matches!(
+            export_project_ndjson(app.state(), "missing".to_string(), file.path()),
+            Err(AppError::NotFound(_))
+        ));
+
+        delete_project(app.state(), project.id.clone()).unwrap();
+        assert!(matches!(
+            export_project_ndjson(app.state(), project.id, file.path()),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn rename_project_emits_the_old_and_new_name() {
+        let app = TestApp::new();
+        let events = app.events(PROJECT_RENAMED_EVENT);
+        let project = app.project("One");
+        app.project("Two");
+
+        let renamed = rename_project(
+            app.handle(),
+            app.state(),
+            project.id.clone(),
+            "  Renamed ".to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(renamed.name, "Renamed");
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["id"], project.id.as_str());
+        assert_eq!(events[0]["old_name"], "One");
+        assert_eq!(events[0]["name"], "Renamed");
+    }
+
+    #[test]
+    fn rename_project_rejects_blank_and_taken_names() {
+        let app = TestApp::new();
+        let first = app.project("First");
+        let second = app.project("Second");
+        // Older databases can hold duplicate names; only renames enforce uniqueness
+        let duplicate = app.project("First");
+        let rename = |id: &str, name: &str| {
+            rename_project(app.handle(), app.state(), id.to_string(), name.to_string())
+        };
+
+        for taken in [" first ", "FIRST", "  "] {
+            assert!(matches!(
+                rename(&second.id, taken),
+                Err(AppError::InvalidInput(_))
+            ));
+        }
+        // A project can always take a new spelling of its own name
+        assert_eq!(rename(&second.id, "SECOND").unwrap().name, "SECOND");
+        assert_eq!(rename(&duplicate.id, "Third").unwrap().name, "Third");
+
+        let unchanged = UpdateProject {
+            name: Some("First".to_string()),
+        };
+        assert!(update_project(app.state(), first.id, unchanged).is_ok());
+    }
+}
@@ -0,0 +1,206 @@
+use super::projects::map_project;
+use crate::error::Result;
+use crate::models::{Project, SearchHit};
+use crate::AppState;
+use std::sync::Arc;
+use tauri::ipc::Channel;
+use tauri::State;
+
+/// Default cap on the number of hits emitted by a streaming search
+const DEFAULT_SEARCH_LIMIT: u32 = 100;
+
+/// Search active nodes, sending each hit over the channel as soon as it is read
+/// Hits are ordered by relevance. Returns the number of hits sent; stops early if the
+/// frontend has dropped the channel.
+#[tauri::command]
+pub fn search_nodes_streaming(
+    state: State<Arc<AppState>>,
+    query: String,
+    limit: Option<u32>,
+    channel: Channel<SearchHit>,
+) -> Result<u32> {
+    let Some(match_expr) = fts_match_expression(&query) else {
+        return Ok(0);
+    };
+
+    let conn = state.db.conn();
+
+    let mut stmt = conn.prepare(
+        "SELECT n.id, n.tree_id, snippet(nodes_fts, -1, '[', ']', '…', 12)
+         FROM nodes_fts
+         INNER JOIN nodes n ON n.rowid = nodes_fts.rowid
+         INNER JOIN trees t ON t.id = n.tree_id
+         WHERE nodes_fts MATCH ?1
+           AND n.deleted_at IS NULL
+           AND t.deleted_at IS NULL
+         ORDER BY rank
+         LIMIT ?2",
+    )?;
+
+    let hits = stmt.query_map(
+        (&match_expr, limit.unwrap_or(DEFAULT_SEARCH_LIMIT)),
+        |row| {
+            Ok(SearchHit {
+                node_id: row.get(0)?,
+                tree_id: row.get(1)?,
+                snippet: row.get(2)?,
+            })
+        },
+    )?;
+
+    let mut sent = 0;
+    for hit in hits {
+        if channel.send(hit?).is_err() {
+            break;
+        }
+        sent += 1;
+    }
+
+    Ok(sent)
+}
+
+/// List active projects that have at least one active node matching the query
+#[tauri::command]
+pub fn projects_with_node_match(
+    state: State<Arc<AppState>>,
+    query: String,
+) -> Result<Vec<Project>> {
+    let Some(match_expr) = fts_match_expression(&query) else {
+        return Ok(Vec::new());
+    };
+
+    let conn = state.db.conn();
+
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT p.id, p.name, p.created_at, p.updated_at, p.deleted_at
+         FROM nodes_fts
+         INNER JOIN nodes n ON n.rowid = nodes_fts.rowid
+         INNER JOIN trees t ON t.id = n.tree_id
+         INNER JOIN projects p ON p.id = t.project_id
+         WHERE nodes_fts MATCH ?1
+           AND n.deleted_at IS NULL
+           AND t.deleted_at IS NULL
+           AND p.deleted_at IS NULL
+         ORDER BY p.created_at DESC",
+    )?;
+
+    let projects = stmt
+        .query_map([&match_expr], map_project)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(projects)
+}
+
+/// Build an FTS5 match expression that treats every word of the query as a literal term
+/// Returns None if the query has no words
+fn fts_match_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::nodes::create_node;
+    use crate::commands::test_support::{new_node, TestApp};
+    use crate::commands::trees::delete_tree;
+    use std::sync::Mutex;
+
+    /// Helper function to make a channel that collects every hit sent over it
+    fn collecting_channel() -> (Channel<SearchHit>, Arc<Mutex<Vec<SearchHit>>>) {
+        let hits = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&hits);
+        let channel = Channel::new(move |body| {
+            sink.lock().unwrap().push(body.deserialize().unwrap());
+            Ok(())
+        });
+        (channel, hits)
+    }
+
+    fn app_with_nodes(contents: &[&str]) -> TestApp {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        for content in contents {
+            create_node(app.state(), new_node(&tree.id, None, content)).unwrap();
+        }
+        app
+    }
+
+    #[test]
+    fn streaming_search_sends_hits_by_relevance() {
+        let app = app_with_nodes(&["the cat sat", "a dog barked", "cat cat cat everywhere"]);
+        let (channel, hits) = collecting_channel();
+
+        let sent = search_nodes_streaming(app.state(), "cat".to_string(), None, channel).unwrap();
+
+        let hits = hits.lock().unwrap();
+        assert_eq!(sent, 2);
+        assert_eq!(hits.len(), 2);
+        assert!(hits[0].snippet.contains("everywhere"));
+    }
+
+    #[test]
+    fn streaming_search_respects_limit_and_quotes_terms() {
+        let app = app_with_nodes(&["the cat sat", "cat cat cat everywhere", "say \"hi\""]);
+
+        let (channel, _) = collecting_channel();
+        assert_eq!(
+            search_nodes_streaming(app.state(), "cat".to_string(), Some(1), channel).unwrap(),
+            1
+        );
+        let (channel, _) = collecting_channel();
+        assert_eq!(
+            search_nodes_streaming(app.state(), "\"hi\" OR (".to_string(), None, channel).unwrap(),
+            0
+        );
+        let (channel, _) = collecting_channel();
+        assert_eq!(
+            search_nodes_streaming(app.state(), "   ".to_string(), None, channel).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn streaming_search_stops_when_channel_is_gone() {
+        let app = app_with_nodes(&["the cat sat", "cat cat cat everywhere"]);
+        let channel = Channel::new(|_| Err(tauri::Error::FailedToReceiveMessage));
+
+        assert_eq!(
+            search_nodes_streaming(app.state(), "cat".to_string(), None, channel).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn projects_with_node_match_skips_trashed_trees() {
+        let app = TestApp::new();
+        let striped = app.project("Striped");
+        let maned = app.project("Maned");
+        let zebras = app.tree(Some(&striped.id), "Zebras");
+        let lions = app.tree(Some(&maned.id), "Lions");
+        for (tree_id, content) in [
+            (&zebras.id, "zebra stripes"),
+            (&zebras.id, "zebra again"),
+            (&lions.id, "lion"),
+        ] {
+            create_node(app.state(), new_node(tree_id, None, content)).unwrap();
+        }
+
+        let projects = projects_with_node_match(app.state(), "zebra".to_string()).unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].id, striped.id);
+
+        delete_tree(app.state(), zebras.id).unwrap();
+        assert!(projects_with_node_match(app.state(), "zebra".to_string())
+            .unwrap()
+            .is_empty());
+    }
+}
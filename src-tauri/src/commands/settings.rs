@@ -1,8 +1,11 @@
 use crate::error::{AppError, Result};
-use crate::models::Setting;
+use crate::models::{Setting, SettingsChanged, TreeSetting};
 use crate::AppState;
 use std::sync::Arc;
-use tauri::State;
+use tauri::{AppHandle, Emitter, Runtime, State};
+
+/// Event emitted after settings have been changed in bulk
+pub const SETTINGS_CHANGED_EVENT: &str = "settings:changed";
 
 /// Get a setting by key
 #[tauri::command]
@@ -10,16 +13,11 @@ pub fn get_setting(state: State<Arc<AppState>>, key: String) -> Result<Setting>
     let conn = state.db.conn();
 
     conn.query_row(
-        "SELECT key, value, created_at, updated_at FROM settings WHERE key = ?1",
+        "SELECT key, value, created_at, updated_at, deleted_at
+         FROM settings
+         WHERE key = ?1 AND deleted_at IS NULL",
         [&key],
-        |row| {
-            Ok(Setting {
-                key: row.get(0)?,
-                value: row.get(1)?,
-                created_at: row.get(2)?,
-                updated_at: row.get(3)?,
-            })
-        },
+        map_setting,
     )
     .map_err(|e| match e {
         rusqlite::Error::QueryReturnedNoRows => {
@@ -33,74 +31,139 @@ pub fn get_setting(state: State<Arc<AppState>>, key: String) -> Result<Setting>
 #[tauri::command]
 pub fn get_setting_value(state: State<Arc<AppState>>, key: String) -> Result<Option<String>> {
     let conn = state.db.conn();
+    read_setting(&conn, &key)
+}
 
-    let result = conn.query_row("SELECT value FROM settings WHERE key = ?1", [&key], |row| {
-        row.get::<_, String>(0)
-    });
-
-    match result {
-        Ok(value) => Ok(Some(value)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(AppError::Database(e)),
-    }
+/// Get a setting value by key, returning the given default if not found
+#[tauri::command]
+pub fn get_setting_value_or(
+    state: State<Arc<AppState>>,
+    key: String,
+    default: String,
+) -> Result<String> {
+    let conn = state.db.conn();
+    Ok(read_setting(&conn, &key)?.unwrap_or(default))
 }
 
 /// Set a setting (insert or update)
+/// Setting a key that is in the trash restores it with the new value
 #[tauri::command]
 pub fn set_setting(state: State<Arc<AppState>>, key: String, value: String) -> Result<Setting> {
     let conn = state.db.conn();
 
-    // Use INSERT OR REPLACE (UPSERT) pattern
-    conn.execute(
-        "INSERT INTO settings (key, value, created_at, updated_at)
-         VALUES (?1, ?2, datetime('now'), NULL)
-         ON CONFLICT(key) DO UPDATE SET
-             value = excluded.value,
-             updated_at = datetime('now')",
-        (&key, &value),
-    )?;
+    upsert_setting(&conn, &key, &value)?;
 
     // Return the setting
-    conn.query_row(
-        "SELECT key, value, created_at, updated_at FROM settings WHERE key = ?1",
-        [&key],
-        |row| {
-            Ok(Setting {
-                key: row.get(0)?,
-                value: row.get(1)?,
-                created_at: row.get(2)?,
-                updated_at: row.get(3)?,
-            })
-        },
-    )
-    .map_err(AppError::Database)
+    get_setting_by_key(&conn, &key)
 }
 
-/// List all settings
+/// Set many settings at once in a single transaction
+/// Emits one `settings:changed` event listing the affected keys; returns how many were set.
+#[tauri::command]
+pub fn set_settings<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<Arc<AppState>>,
+    entries: Vec<(String, String)>,
+) -> Result<usize> {
+    let mut conn = state.db.conn();
+    let tx = conn.transaction()?;
+
+    let mut keys: Vec<String> = Vec::with_capacity(entries.len());
+    for (key, value) in entries {
+        upsert_setting(&tx, &key, &value)?;
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    tx.commit()?;
+
+    let count = keys.len();
+    if count > 0 {
+        // The settings are already committed, so a failed emit is not reported as an error
+        let _ = app.emit(SETTINGS_CHANGED_EVENT, SettingsChanged { keys });
+    }
+
+    Ok(count)
+}
+
+/// List all active (non-deleted) settings
 #[tauri::command]
 pub fn list_settings(state: State<Arc<AppState>>) -> Result<Vec<Setting>> {
     let conn = state.db.conn();
 
-    let mut stmt =
-        conn.prepare("SELECT key, value, created_at, updated_at FROM settings ORDER BY key ASC")?;
+    let mut stmt = conn.prepare(
+        "SELECT key, value, created_at, updated_at, deleted_at
+         FROM settings
+         WHERE deleted_at IS NULL
+         ORDER BY key ASC",
+    )?;
+
+    let settings = stmt
+        .query_map([], map_setting)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(settings)
+}
+
+/// List deleted settings (trash)
+#[tauri::command]
+pub fn list_deleted_settings(state: State<Arc<AppState>>) -> Result<Vec<Setting>> {
+    let conn = state.db.conn();
+
+    let mut stmt = conn.prepare(
+        "SELECT key, value, created_at, updated_at, deleted_at
+         FROM settings
+         WHERE deleted_at IS NOT NULL
+         ORDER BY deleted_at DESC",
+    )?;
 
     let settings = stmt
-        .query_map([], |row| {
-            Ok(Setting {
-                key: row.get(0)?,
-                value: row.get(1)?,
-                created_at: row.get(2)?,
-                updated_at: row.get(3)?,
-            })
-        })?
+        .query_map([], map_setting)?
         .collect::<std::result::Result<Vec<_>, _>>()?;
 
     Ok(settings)
 }
 
-/// Delete a setting
+/// Soft delete a setting (move to trash)
+#[tauri::command]
+pub fn delete_setting(state: State<Arc<AppState>>, key: String) -> Result<Setting> {
+    let conn = state.db.conn();
+
+    let rows_affected = conn.execute(
+        "UPDATE settings SET deleted_at = datetime('now'), updated_at = datetime('now') WHERE key = ?1 AND deleted_at IS NULL",
+        (&key,),
+    )?;
+
+    if rows_affected == 0 {
+        return Err(AppError::NotFound(format!("Setting '{key}' not found")));
+    }
+
+    get_setting_by_key(&conn, &key)
+}
+
+/// Restore a setting from trash
 #[tauri::command]
-pub fn delete_setting(state: State<Arc<AppState>>, key: String) -> Result<()> {
+pub fn restore_setting(state: State<Arc<AppState>>, key: String) -> Result<Setting> {
+    let conn = state.db.conn();
+
+    let rows_affected = conn.execute(
+        "UPDATE settings SET deleted_at = NULL, updated_at = datetime('now') WHERE key = ?1 AND deleted_at IS NOT NULL",
+        (&key,),
+    )?;
+
+    if rows_affected == 0 {
+        return Err(AppError::NotFound(format!(
+            "Deleted setting '{key}' not found"
+        )));
+    }
+
+    get_setting_by_key(&conn, &key)
+}
+
+/// Permanently delete a setting (cannot be undone)
+#[tauri::command]
+pub fn permanently_delete_setting(state: State<Arc<AppState>>, key: String) -> Result<()> {
     let conn = state.db.conn();
 
     let rows_affected = conn.execute("DELETE FROM settings WHERE key = ?1", (&key,))?;
@@ -111,3 +174,307 @@ pub fn delete_setting(state: State<Arc<AppState>>, key: String) -> Result<()> {
 
     Ok(())
 }
+
+/// Get a tree-scoped setting by key
+#[tauri::command]
+pub fn get_tree_setting(
+    state: State<Arc<AppState>>,
+    tree_id: String,
+    key: String,
+) -> Result<TreeSetting> {
+    let conn = state.db.conn();
+    get_tree_setting_by_key(&conn, &tree_id, &key)
+}
+
+/// Set a tree-scoped setting (insert or update)
+#[tauri::command]
+pub fn set_tree_setting(
+    state: State<Arc<AppState>>,
+    tree_id: String,
+    key: String,
+    value: String,
+) -> Result<TreeSetting> {
+    let conn = state.db.conn();
+
+    let tree_exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM trees WHERE id = ?1)",
+        [&tree_id],
+        |row| row.get(0),
+    )?;
+    if !tree_exists {
+        return Err(AppError::NotFound(format!("Tree {tree_id} not found")));
+    }
+
+    // Same UPSERT semantics as global settings
+    conn.execute(
+        "INSERT INTO tree_settings (tree_id, key, value, created_at, updated_at)
+         VALUES (?1, ?2, ?3, datetime('now'), datetime('now'))
+         ON CONFLICT(tree_id, key) DO UPDATE SET
+             value = excluded.value,
+             updated_at = datetime('now')",
+        (&tree_id, &key, &value),
+    )?;
+
+    get_tree_setting_by_key(&conn, &tree_id, &key)
+}
+
+/// List all settings for a tree
+#[tauri::command]
+pub fn list_tree_settings(
+    state: State<Arc<AppState>>,
+    tree_id: String,
+) -> Result<Vec<TreeSetting>> {
+    let conn = state.db.conn();
+
+    let mut stmt = conn.prepare(
+        "SELECT tree_id, key, value, created_at, updated_at
+         FROM tree_settings
+         WHERE tree_id = ?1
+         ORDER BY key ASC",
+    )?;
+
+    let settings = stmt
+        .query_map([&tree_id], map_tree_setting)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(settings)
+}
+
+/// Helper function to read an active setting's value, for commands that consult settings
+pub(crate) fn read_setting(
+    conn: &std::sync::MutexGuard<'_, rusqlite::Connection>,
+    key: &str,
+) -> Result<Option<String>> {
+    let result = conn.query_row(
+        "SELECT value FROM settings WHERE key = ?1 AND deleted_at IS NULL",
+        [key],
+        |row| row.get::<_, String>(0),
+    );
+
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(AppError::Database(e)),
+    }
+}
+
+/// Helper function to upsert a setting, restoring it if it is in the trash
+fn upsert_setting(conn: &rusqlite::Connection, key: &str, value: &str) -> Result<()> {
+    // UPSERT rather than INSERT OR REPLACE so created_at survives updates.
+    // A fresh setting starts with updated_at equal to created_at, like projects and trees.
+    conn.execute(
+        "INSERT INTO settings (key, value, created_at, updated_at)
+         VALUES (?1, ?2, datetime('now'), datetime('now'))
+         ON CONFLICT(key) DO UPDATE SET
+             value = excluded.value,
+             updated_at = datetime('now'),
+             deleted_at = NULL",
+        (key, value),
+    )?;
+    Ok(())
+}
+
+/// Helper function to map a row to a Setting
+fn map_setting(row: &rusqlite::Row<'_>) -> rusqlite::Result<Setting> {
+    Ok(Setting {
+        key: row.get(0)?,
+        value: row.get(1)?,
+        created_at: row.get(2)?,
+        updated_at: row.get(3)?,
+        deleted_at: row.get(4)?,
+    })
+}
+
+/// Helper function to get a setting by key, including deleted ones
+fn get_setting_by_key(
+    conn: &std::sync::MutexGuard<'_, rusqlite::Connection>,
+    key: &str,
+) -> Result<Setting> {
+    conn.query_row(
+        "SELECT key, value, created_at, updated_at, deleted_at FROM settings WHERE key = ?1",
+        [key],
+        map_setting,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+            AppError::NotFound(format!("Setting '{key}' not found"))
+        }
+        _ => AppError::Database(e),
+    })
+}
+
+/// Helper function to map a row to a `TreeSetting`
+fn map_tree_setting(row: &rusqlite::Row<'_>) -> rusqlite::Result<TreeSetting> {
+    Ok(TreeSetting {
+        tree_id: row.get(0)?,
+        key: row.get(1)?,
+        value: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+/// Helper function to get a tree-scoped setting by key
+fn get_tree_setting_by_key(
+    conn: &std::sync::MutexGuard<'_, rusqlite::Connection>,
+    tree_id: &str,
+    key: &str,
+) -> Result<TreeSetting> {
+    conn.query_row(
+        "SELECT tree_id, key, value, created_at, updated_at FROM tree_settings WHERE tree_id = ?1 AND key = ?2",
+        [tree_id, key],
+        map_tree_setting,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+            AppError::NotFound(format!("Setting '{key}' not found for tree {tree_id}"))
+        }
+        _ => AppError::Database(e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::TestApp;
+    use crate::commands::trees::permanently_delete_tree;
+
+    #[test]
+    fn deleted_setting_can_be_restored_or_purged() {
+        let app = TestApp::new();
+        set_setting(app.state(), "theme".to_string(), "dark".to_string()).unwrap();
+
+        let deleted = delete_setting(app.state(), "theme".to_string()).unwrap();
+        assert!(deleted.deleted_at.is_some());
+        assert!(get_setting(app.state(), "theme".to_string()).is_err());
+        assert_eq!(
+            get_setting_value(app.state(), "theme".to_string()).unwrap(),
+            None
+        );
+        assert!(list_settings(app.state()).unwrap().is_empty());
+        assert_eq!(list_deleted_settings(app.state()).unwrap().len(), 1);
+
+        let restored = restore_setting(app.state(), "theme".to_string()).unwrap();
+        assert_eq!(restored.value, "dark");
+        assert_eq!(restored.deleted_at, None);
+
+        delete_setting(app.state(), "theme".to_string()).unwrap();
+        permanently_delete_setting(app.state(), "theme".to_string()).unwrap();
+        assert!(list_deleted_settings(app.state()).unwrap().is_empty());
+        assert!(matches!(
+            restore_setting(app.state(), "theme".to_string()),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn set_setting_stamps_updated_at_and_keeps_created_at() {
+        let app = TestApp::new();
+        let created = set_setting(app.state(), "theme".to_string(), "dark".to_string()).unwrap();
+        assert_eq!(
+            created.updated_at.as_deref(),
+            Some(created.created_at.as_str())
+        );
+
+        app.execute("UPDATE settings SET created_at = '2000-01-01 00:00:00'", []);
+        let updated = set_setting(app.state(), "theme".to_string(), "light".to_string()).unwrap();
+
+        assert_eq!(updated.value, "light");
+        assert_eq!(updated.created_at, "2000-01-01 00:00:00");
+    }
+
+    #[test]
+    fn tree_settings_are_upserted_and_go_with_the_tree() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        set_tree_setting(
+            app.state(),
+            tree.id.clone(),
+            "view".to_string(),
+            "compact".to_string(),
+        )
+        .unwrap();
+        set_tree_setting(
+            app.state(),
+            tree.id.clone(),
+            "view".to_string(),
+            "wide".to_string(),
+        )
+        .unwrap();
+
+        let setting = get_tree_setting(app.state(), tree.id.clone(), "view".to_string()).unwrap();
+        assert_eq!(setting.value, "wide");
+        assert_eq!(
+            list_tree_settings(app.state(), tree.id.clone())
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(set_tree_setting(
+            app.state(),
+            "missing".to_string(),
+            "view".to_string(),
+            "wide".to_string()
+        )
+        .is_err());
+
+        permanently_delete_tree(app.state(), tree.id.clone()).unwrap();
+        assert!(get_tree_setting(app.state(), tree.id, "view".to_string()).is_err());
+    }
+
+    #[test]
+    fn setting_value_or_falls_back_to_default() {
+        let app = TestApp::new();
+        set_setting(app.state(), "theme".to_string(), "dark".to_string()).unwrap();
+        set_setting(app.state(), "font".to_string(), "mono".to_string()).unwrap();
+        delete_setting(app.state(), "font".to_string()).unwrap();
+
+        let value_or = |key: &str| {
+            get_setting_value_or(app.state(), key.to_string(), "default".to_string()).unwrap()
+        };
+        assert_eq!(value_or("theme"), "dark");
+        assert_eq!(value_or("font"), "default");
+        assert_eq!(value_or("missing"), "default");
+    }
+
+    #[test]
+    fn set_settings_writes_all_entries_and_emits_once() {
+        let app = TestApp::new();
+        let events = app.events(SETTINGS_CHANGED_EVENT);
+        set_setting(app.state(), "k0".to_string(), "old".to_string()).unwrap();
+        delete_setting(app.state(), "k0".to_string()).unwrap();
+
+        let entries: Vec<(String, String)> =
+            (0..3).map(|i| (format!("k{i}"), format!("v{i}"))).collect();
+        assert_eq!(set_settings(app.handle(), app.state(), entries).unwrap(), 3);
+        for i in 0..3 {
+            let setting = get_setting(app.state(), format!("k{i}")).unwrap();
+            assert_eq!(setting.value, format!("v{i}"));
+        }
+        assert_eq!(events.lock().unwrap().len(), 1);
+        assert_eq!(
+            events.lock().unwrap()[0]["keys"],
+            serde_json::json!(["k0", "k1", "k2"])
+        );
+
+        assert_eq!(
+            set_settings(app.handle(), app.state(), Vec::new()).unwrap(),
+            0
+        );
+        assert_eq!(events.lock().unwrap().len(), 1);
+
+        // A repeated key counts once and keeps its last value
+        let repeated = vec![
+            ("a".to_string(), "1".to_string()),
+            ("a".to_string(), "2".to_string()),
+        ];
+        assert_eq!(
+            set_settings(app.handle(), app.state(), repeated).unwrap(),
+            1
+        );
+        assert_eq!(
+            get_setting(app.state(), "a".to_string()).unwrap().value,
+            "2"
+        );
+    }
+}
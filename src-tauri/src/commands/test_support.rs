@@ -0,0 +1,131 @@
+use super::nodes::create_node;
+use super::projects::create_project;
+use super::trees::create_tree;
+use crate::db::Database;
+use crate::models::{CreateNode, CreateProject, CreateTree, Node, Project, Tree};
+use crate::AppState;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::test::{mock_app, MockRuntime};
+use tauri::{App, AppHandle, Listener, Manager, State};
+use uuid::Uuid;
+
+/// `TestApp` - a mock app managing its own freshly migrated database
+pub struct TestApp {
+    app: App<MockRuntime>,
+}
+
+impl TestApp {
+    /// Create an app backed by an in-memory database
+    pub fn new() -> Self {
+        Self::with_database(PathBuf::from(":memory:"))
+    }
+
+    /// Create an app backed by a database file, which outlives the app
+    pub fn open(file: &TempPath) -> Self {
+        Self::with_database(file.0.clone())
+    }
+
+    fn with_database(path: PathBuf) -> Self {
+        let app = mock_app();
+        app.manage(Arc::new(AppState {
+            db: Database::new(path).expect("Failed to open test database"),
+        }));
+        Self { app }
+    }
+
+    pub fn state(&self) -> State<'_, Arc<AppState>> {
+        self.app.state()
+    }
+
+    pub fn handle(&self) -> AppHandle<MockRuntime> {
+        self.app.handle().clone()
+    }
+
+    /// Collect the payloads of every `event` emitted from now on
+    pub fn events(&self, event: &str) -> Arc<Mutex<Vec<serde_json::Value>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        self.app.listen_any(event, move |event| {
+            let payload = serde_json::from_str(event.payload()).expect("Event payload is not JSON");
+            sink.lock().unwrap().push(payload);
+        });
+        events
+    }
+
+    /// Run a statement directly, e.g. to backdate rows
+    pub fn execute(&self, sql: &str, params: impl rusqlite::Params) {
+        self.state()
+            .db
+            .conn()
+            .execute(sql, params)
+            .expect("Failed to run test statement");
+    }
+
+    pub fn project(&self, name: &str) -> Project {
+        create_project(
+            self.state(),
+            CreateProject {
+                name: name.to_string(),
+            },
+        )
+        .expect("Failed to create test project")
+    }
+
+    pub fn tree(&self, project_id: Option<&str>, name: &str) -> Tree {
+        create_tree(
+            self.state(),
+            CreateTree {
+                project_id: project_id.map(ToString::to_string),
+                name: name.to_string(),
+                system_prompt: None,
+                default_model: None,
+            },
+        )
+        .expect("Failed to create test tree")
+    }
+
+    pub fn node(&self, tree_id: &str, parent_id: Option<&str>, user_content: &str) -> Node {
+        create_node(self.state(), new_node(tree_id, parent_id, user_content))
+            .expect("Failed to create test node")
+    }
+}
+
+/// Input for a plain user turn, to adjust before creating
+pub fn new_node(tree_id: &str, parent_id: Option<&str>, user_content: &str) -> CreateNode {
+    CreateNode {
+        tree_id: tree_id.to_string(),
+        parent_id: parent_id.map(ToString::to_string),
+        user_content: user_content.to_string(),
+        assistant_content: None,
+        summary: None,
+        model: None,
+        tokens: None,
+        params: None,
+        idempotency_key: None,
+    }
+}
+
+/// `TempPath` - a unique path in the temp directory, removed again on drop
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    pub fn new(extension: &str) -> Self {
+        let name = format!("tangential-test-{}.{extension}", Uuid::new_v4());
+        Self(std::env::temp_dir().join(name))
+    }
+
+    pub fn path(&self) -> String {
+        self.0.to_string_lossy().into_owned()
+    }
+
+    pub fn read(&self) -> String {
+        std::fs::read_to_string(&self.0).expect("Failed to read test file")
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
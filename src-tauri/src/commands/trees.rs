@@ -1,19 +1,44 @@
+use super::nodes::{map_node, params_to_json};
+use super::settings::read_setting;
+use crate::db::hash::content_hash;
 use crate::error::{AppError, Result};
-use crate::models::{CreateTree, Tree, UpdateTree};
+use crate::models::{
+    CreateTree, Node, Patch, Tree, TreeSimilarity, TreeSnapshot, TreeSnapshotData, TreeSort,
+    UpdateTree,
+};
 use crate::AppState;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use tauri::State;
 use uuid::Uuid;
 
+/// Setting that caps the length of a tree's system prompt, in characters
+pub const MAX_SYSTEM_PROMPT_CHARS_SETTING: &str = "tree.max_system_prompt_chars";
+
+/// Limit used when the setting is missing or not a positive number
+const DEFAULT_MAX_SYSTEM_PROMPT_CHARS: usize = 100_000;
+
 /// Create a new tree
 #[tauri::command]
 pub fn create_tree(state: State<Arc<AppState>>, input: CreateTree) -> Result<Tree> {
     let conn = state.db.conn();
     let id = Uuid::new_v4().to_string();
 
+    if let Some(ref system_prompt) = input.system_prompt {
+        validate_system_prompt(&conn, system_prompt)?;
+    }
+
     conn.execute(
-        "INSERT INTO trees (id, project_id, name, system_prompt) VALUES (?1, ?2, ?3, ?4)",
-        (&id, &input.project_id, &input.name, &input.system_prompt),
+        "INSERT INTO trees (id, project_id, name, system_prompt, default_model) VALUES (?1, ?2, ?3, ?4, ?5)",
+        (
+            &id,
+            &input.project_id,
+            &input.name,
+            &input.system_prompt,
+            &input.default_model,
+        ),
     )?;
 
     get_tree_by_id(&conn, &id)
@@ -28,27 +53,32 @@ pub fn get_tree(state: State<Arc<AppState>>, id: String) -> Result<Tree> {
 
 /// List all active (non-deleted) trees, optionally filtered by project
 #[tauri::command]
-pub fn list_trees(state: State<Arc<AppState>>, project_id: Option<String>) -> Result<Vec<Tree>> {
+pub fn list_trees(
+    state: State<Arc<AppState>>,
+    project_id: Option<String>,
+    sort: Option<TreeSort>,
+) -> Result<Vec<Tree>> {
     let conn = state.db.conn();
+    let order_by = sort.unwrap_or_default().order_by();
 
     let trees = if let Some(pid) = project_id {
-        let mut stmt = conn.prepare(
-            "SELECT id, project_id, name, system_prompt, created_at, updated_at, deleted_at
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, project_id, name, system_prompt, created_at, updated_at, deleted_at, default_model
              FROM trees
              WHERE project_id = ?1 AND deleted_at IS NULL
-             ORDER BY created_at DESC",
-        )?;
+             ORDER BY {order_by}"
+        ))?;
         let result = stmt
             .query_map([&pid], map_tree)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
         result
     } else {
-        let mut stmt = conn.prepare(
-            "SELECT id, project_id, name, system_prompt, created_at, updated_at, deleted_at
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, project_id, name, system_prompt, created_at, updated_at, deleted_at, default_model
              FROM trees
              WHERE deleted_at IS NULL
-             ORDER BY created_at DESC",
-        )?;
+             ORDER BY {order_by}"
+        ))?;
         let result = stmt
             .query_map([], map_tree)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
@@ -60,15 +90,19 @@ pub fn list_trees(state: State<Arc<AppState>>, project_id: Option<String>) -> Re
 
 /// List trees in staging (no project assigned)
 #[tauri::command]
-pub fn list_staging_trees(state: State<Arc<AppState>>) -> Result<Vec<Tree>> {
+pub fn list_staging_trees(
+    state: State<Arc<AppState>>,
+    sort: Option<TreeSort>,
+) -> Result<Vec<Tree>> {
     let conn = state.db.conn();
+    let order_by = sort.unwrap_or_default().order_by();
 
-    let mut stmt = conn.prepare(
-        "SELECT id, project_id, name, system_prompt, created_at, updated_at, deleted_at
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, project_id, name, system_prompt, created_at, updated_at, deleted_at, default_model
          FROM trees
          WHERE project_id IS NULL AND deleted_at IS NULL
-         ORDER BY created_at DESC",
-    )?;
+         ORDER BY {order_by}"
+    ))?;
 
     let trees = stmt
         .query_map([], map_tree)?
@@ -83,7 +117,7 @@ pub fn list_deleted_trees(state: State<Arc<AppState>>) -> Result<Vec<Tree>> {
     let conn = state.db.conn();
 
     let mut stmt = conn.prepare(
-        "SELECT id, project_id, name, system_prompt, created_at, updated_at, deleted_at
+        "SELECT id, project_id, name, system_prompt, created_at, updated_at, deleted_at, default_model
          FROM trees
          WHERE deleted_at IS NOT NULL
          ORDER BY deleted_at DESC",
@@ -111,17 +145,24 @@ pub fn update_tree(state: State<Arc<AppState>>, id: String, input: UpdateTree) -
     let mut updates = vec!["updated_at = datetime('now')".to_string()];
     let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![];
 
-    if let Some(ref project_id) = input.project_id {
+    if let Some(project_id) = input.project_id.into_change() {
         updates.push(format!("project_id = ?{}", params.len() + 1));
-        params.push(Box::new(project_id.clone()));
+        params.push(Box::new(project_id));
     }
     if let Some(ref name) = input.name {
         updates.push(format!("name = ?{}", params.len() + 1));
         params.push(Box::new(name.clone()));
     }
-    if let Some(ref system_prompt) = input.system_prompt {
+    if let Patch::Set(ref system_prompt) = input.system_prompt {
+        validate_system_prompt(&conn, system_prompt)?;
+    }
+    if let Some(system_prompt) = input.system_prompt.into_change() {
         updates.push(format!("system_prompt = ?{}", params.len() + 1));
-        params.push(Box::new(system_prompt.clone()));
+        params.push(Box::new(system_prompt));
+    }
+    if let Some(default_model) = input.default_model.into_change() {
+        updates.push(format!("default_model = ?{}", params.len() + 1));
+        params.push(Box::new(default_model));
     }
 
     let query = format!(
@@ -138,6 +179,258 @@ pub fn update_tree(state: State<Arc<AppState>>, id: String, input: UpdateTree) -
     get_tree_by_id(&conn, &id)
 }
 
+/// Move every staging tree into a project
+/// Returns the number of trees moved
+#[tauri::command]
+pub fn file_all_staging(state: State<Arc<AppState>>, project_id: String) -> Result<usize> {
+    let mut conn = state.db.conn();
+    let tx = conn.transaction()?;
+
+    let project_is_live: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM projects WHERE id = ?1 AND deleted_at IS NULL)",
+        [&project_id],
+        |row| row.get(0),
+    )?;
+    if !project_is_live {
+        return Err(AppError::NotFound(format!(
+            "Project {project_id} not found"
+        )));
+    }
+
+    let filed = tx.execute(
+        "UPDATE trees SET project_id = ?1, updated_at = datetime('now')
+         WHERE project_id IS NULL AND deleted_at IS NULL",
+        [&project_id],
+    )?;
+
+    tx.commit()?;
+
+    Ok(filed)
+}
+
+/// Get the system prompt that applies to a tree
+/// The tree's own prompt is the only level configured today; blank prompts count as unset
+#[tauri::command]
+pub fn get_effective_system_prompt(
+    state: State<Arc<AppState>>,
+    tree_id: String,
+) -> Result<Option<String>> {
+    let conn = state.db.conn();
+
+    let tree = get_tree_by_id(&conn, &tree_id)?;
+    if tree.deleted_at.is_some() {
+        return Err(AppError::NotFound(format!("Tree {tree_id} is deleted")));
+    }
+
+    Ok(tree
+        .system_prompt
+        .filter(|prompt| !prompt.trim().is_empty()))
+}
+
+/// Number of consecutive words in each shingle used for similarity
+const SHINGLE_WORDS: usize = 3;
+
+/// Find active trees whose content is similar to a tree's
+/// Each tree's active node content is reduced to a set of hashed word shingles and
+/// compared with Jaccard similarity. Returns trees scoring at or above `threshold`,
+/// most similar first.
+#[tauri::command]
+pub fn find_similar_trees(
+    state: State<Arc<AppState>>,
+    tree_id: String,
+    threshold: f64,
+) -> Result<Vec<TreeSimilarity>> {
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(AppError::InvalidInput(
+            "Threshold must be between 0 and 1".to_string(),
+        ));
+    }
+
+    let conn = state.db.conn();
+
+    let tree = get_tree_by_id(&conn, &tree_id)?;
+    if tree.deleted_at.is_some() {
+        return Err(AppError::NotFound(format!("Tree {tree_id} is deleted")));
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.name, n.user_content, n.assistant_content
+         FROM trees t
+         INNER JOIN nodes n ON n.tree_id = t.id
+         WHERE t.deleted_at IS NULL AND n.deleted_at IS NULL",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, Option<String>>(3)?,
+        ))
+    })?;
+
+    let mut names: HashMap<String, String> = HashMap::new();
+    let mut shingles: HashMap<String, HashSet<u64>> = HashMap::new();
+    for row in rows {
+        let (id, name, user_content, assistant_content) = row?;
+        let set = shingles.entry(id.clone()).or_default();
+        add_shingles(set, &user_content);
+        if let Some(assistant_content) = assistant_content {
+            add_shingles(set, &assistant_content);
+        }
+        names.entry(id).or_insert(name);
+    }
+
+    let target = shingles.remove(&tree_id).unwrap_or_default();
+    if target.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut similar: Vec<TreeSimilarity> = shingles
+        .into_iter()
+        .map(|(id, set)| TreeSimilarity {
+            score: jaccard(&target, &set),
+            name: names.remove(&id).unwrap_or_default(),
+            tree_id: id,
+        })
+        .filter(|similarity| similarity.score >= threshold)
+        .collect();
+    similar.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    Ok(similar)
+}
+
+/// Merge one tree into another, then move the emptied source tree to trash
+/// All of the source's nodes (including trashed ones) move to the target. Source roots
+/// are attached under `attach_under`, or become roots of the target if it is omitted.
+/// Returns the target tree.
+#[tauri::command]
+pub fn merge_trees(
+    state: State<Arc<AppState>>,
+    source_id: String,
+    target_id: String,
+    attach_under: Option<String>,
+) -> Result<Tree> {
+    let mut conn = state.db.conn();
+
+    if source_id == target_id {
+        return Err(AppError::InvalidInput(
+            "Cannot merge a tree into itself".to_string(),
+        ));
+    }
+    for id in [&source_id, &target_id] {
+        if get_tree_by_id(&conn, id)?.deleted_at.is_some() {
+            return Err(AppError::NotFound(format!("Tree {id} is deleted")));
+        }
+    }
+
+    if let Some(ref parent_id) = attach_under {
+        let belongs_to_target: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM nodes WHERE id = ?1 AND tree_id = ?2 AND deleted_at IS NULL)",
+            (parent_id, &target_id),
+            |row| row.get(0),
+        )?;
+        if !belongs_to_target {
+            return Err(AppError::InvalidInput(format!(
+                "Node {parent_id} is not an active node in tree {target_id}"
+            )));
+        }
+    }
+
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "UPDATE nodes SET parent_id = ?1, updated_at = datetime('now') WHERE tree_id = ?2 AND parent_id IS NULL",
+        (&attach_under, &source_id),
+    )?;
+    tx.execute(
+        "UPDATE nodes SET tree_id = ?1, updated_at = datetime('now') WHERE tree_id = ?2",
+        (&target_id, &source_id),
+    )?;
+    tx.execute(
+        "UPDATE trees SET deleted_at = datetime('now'), updated_at = datetime('now') WHERE id = ?1",
+        (&source_id,),
+    )?;
+    tx.execute(
+        "UPDATE trees SET updated_at = datetime('now') WHERE id = ?1",
+        (&target_id,),
+    )?;
+
+    tx.commit()?;
+
+    get_tree_by_id(&conn, &target_id)
+}
+
+/// Move a node and its descendants out of their tree into a new tree
+/// The node becomes a root of the new tree, which inherits the source tree's system
+/// prompt and default model. Everything outside the subtree stays in the source tree.
+#[tauri::command]
+pub fn extract_subtree_to_tree(
+    state: State<Arc<AppState>>,
+    root_node_id: String,
+    new_tree_name: String,
+    project_id: Option<String>,
+) -> Result<Tree> {
+    let mut conn = state.db.conn();
+
+    let source_id: String = conn
+        .query_row(
+            "SELECT tree_id FROM nodes WHERE id = ?1 AND deleted_at IS NULL",
+            [&root_node_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => {
+                AppError::NotFound(format!("Node {root_node_id} not found"))
+            }
+            _ => AppError::Database(e),
+        })?;
+
+    if let Some(ref project_id) = project_id {
+        let project_is_live: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM projects WHERE id = ?1 AND deleted_at IS NULL)",
+            [project_id],
+            |row| row.get(0),
+        )?;
+        if !project_is_live {
+            return Err(AppError::NotFound(format!(
+                "Project {project_id} not found"
+            )));
+        }
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "INSERT INTO trees (id, project_id, name, system_prompt, default_model)
+         SELECT ?1, ?2, ?3, system_prompt, default_model FROM trees WHERE id = ?4",
+        (&id, &project_id, &new_tree_name, &source_id),
+    )?;
+    tx.execute(
+        "WITH RECURSIVE subtree AS (
+            SELECT id FROM nodes WHERE id = ?2
+            UNION ALL
+            SELECT n.id FROM nodes n
+            INNER JOIN subtree s ON n.parent_id = s.id
+        )
+        UPDATE nodes SET tree_id = ?1, updated_at = datetime('now')
+        WHERE id IN (SELECT id FROM subtree)",
+        (&id, &root_node_id),
+    )?;
+    tx.execute(
+        "UPDATE nodes SET parent_id = NULL WHERE id = ?1",
+        (&root_node_id,),
+    )?;
+    tx.execute(
+        "UPDATE trees SET updated_at = datetime('now') WHERE id = ?1",
+        (&source_id,),
+    )?;
+
+    tx.commit()?;
+
+    get_tree_by_id(&conn, &id)
+}
+
 /// Soft delete a tree (move to trash)
 #[tauri::command]
 pub fn delete_tree(state: State<Arc<AppState>>, id: String) -> Result<Tree> {
@@ -187,6 +480,272 @@ pub fn permanently_delete_tree(state: State<Arc<AppState>>, id: String) -> Resul
     Ok(())
 }
 
+/// Save the current state of a tree (its active nodes) as a named snapshot
+#[tauri::command]
+pub fn create_tree_snapshot(
+    state: State<Arc<AppState>>,
+    tree_id: String,
+    label: String,
+) -> Result<TreeSnapshot> {
+    let conn = state.db.conn();
+
+    let tree = get_tree_by_id(&conn, &tree_id)?;
+    if tree.deleted_at.is_some() {
+        return Err(AppError::NotFound(format!("Tree {tree_id} is deleted")));
+    }
+
+    let nodes = {
+        let mut stmt = conn.prepare(
+            "SELECT id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, deleted_at, failed, delete_reason_id, params, content_hash
+             FROM nodes
+             WHERE tree_id = ?1 AND deleted_at IS NULL
+             ORDER BY created_at ASC",
+        )?;
+        let nodes = stmt
+            .query_map([&tree_id], map_node)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        nodes
+    };
+
+    let data = serde_json::to_string(&TreeSnapshotData { tree, nodes })
+        .map_err(|e| AppError::InvalidInput(format!("Failed to serialize snapshot: {e}")))?;
+
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO tree_snapshots (id, tree_id, label, data) VALUES (?1, ?2, ?3, ?4)",
+        (&id, &tree_id, &label, &data),
+    )?;
+
+    conn.query_row(
+        "SELECT id, tree_id, label, created_at FROM tree_snapshots WHERE id = ?1",
+        [&id],
+        map_tree_snapshot,
+    )
+    .map_err(AppError::Database)
+}
+
+/// List a tree's snapshots, newest first
+#[tauri::command]
+pub fn list_tree_snapshots(
+    state: State<Arc<AppState>>,
+    tree_id: String,
+) -> Result<Vec<TreeSnapshot>> {
+    let conn = state.db.conn();
+
+    let mut stmt = conn.prepare(
+        "SELECT id, tree_id, label, created_at
+         FROM tree_snapshots
+         WHERE tree_id = ?1
+         ORDER BY created_at DESC, rowid DESC",
+    )?;
+
+    let snapshots = stmt
+        .query_map([&tree_id], map_tree_snapshot)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(snapshots)
+}
+
+/// Restore a snapshot, either into a brand-new tree or over the tree it was taken from
+/// Restoring in place moves nodes created since the snapshot to trash rather than
+/// deleting them. Returns the restored tree.
+#[tauri::command]
+pub fn restore_tree_snapshot(
+    state: State<Arc<AppState>>,
+    snapshot_id: String,
+    as_new_tree: bool,
+) -> Result<Tree> {
+    let mut conn = state.db.conn();
+
+    let data: String = conn
+        .query_row(
+            "SELECT data FROM tree_snapshots WHERE id = ?1",
+            [&snapshot_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => {
+                AppError::NotFound(format!("Snapshot {snapshot_id} not found"))
+            }
+            _ => AppError::Database(e),
+        })?;
+    let snapshot: TreeSnapshotData = serde_json::from_str(&data)
+        .map_err(|e| AppError::InvalidInput(format!("Snapshot {snapshot_id} is corrupt: {e}")))?;
+
+    // Restoring as a new tree gives every row a fresh ID
+    let id_map: HashMap<&str, String> = if as_new_tree {
+        std::iter::once(snapshot.tree.id.as_str())
+            .chain(snapshot.nodes.iter().map(|node| node.id.as_str()))
+            .map(|id| (id, Uuid::new_v4().to_string()))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    let resolve = |id: &str| id_map.get(id).cloned().unwrap_or_else(|| id.to_string());
+    let tree_id = resolve(&snapshot.tree.id);
+
+    let tx = conn.transaction()?;
+
+    if as_new_tree {
+        // The snapshot's project may since have been trashed or removed; file into staging then
+        tx.execute(
+            "INSERT INTO trees (id, project_id, name, system_prompt, default_model)
+             VALUES (?1, (SELECT id FROM projects WHERE id = ?2 AND deleted_at IS NULL), ?3, ?4, ?5)",
+            (
+                &tree_id,
+                &snapshot.tree.project_id,
+                &snapshot.tree.name,
+                &snapshot.tree.system_prompt,
+                &snapshot.tree.default_model,
+            ),
+        )?;
+    } else {
+        let rows_affected = tx.execute(
+            "UPDATE trees SET name = ?1, system_prompt = ?2, default_model = ?3, updated_at = datetime('now') WHERE id = ?4 AND deleted_at IS NULL",
+            (
+                &snapshot.tree.name,
+                &snapshot.tree.system_prompt,
+                &snapshot.tree.default_model,
+                &tree_id,
+            ),
+        )?;
+        if rows_affected == 0 {
+            return Err(AppError::NotFound(format!("Tree {tree_id} not found")));
+        }
+    }
+
+    write_snapshot_nodes(&tx, &snapshot.nodes, &tree_id, resolve)?;
+
+    if !as_new_tree {
+        let snapshot_ids: Vec<&str> = snapshot.nodes.iter().map(|n| n.id.as_str()).collect();
+        let live_ids = {
+            let mut stmt =
+                tx.prepare("SELECT id FROM nodes WHERE tree_id = ?1 AND deleted_at IS NULL")?;
+            let ids = stmt
+                .query_map([&tree_id], |row| row.get::<_, String>(0))?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            ids
+        };
+        for id in live_ids {
+            if !snapshot_ids.contains(&id.as_str()) {
+                tx.execute(
+                    "UPDATE nodes SET deleted_at = datetime('now'), updated_at = datetime('now') WHERE id = ?1",
+                    [&id],
+                )?;
+            }
+        }
+    }
+
+    tx.commit()?;
+
+    get_tree_by_id(&conn, &tree_id)
+}
+
+/// Helper function to add the hashed word shingles of some text to a set
+fn add_shingles(set: &mut HashSet<u64>, text: &str) {
+    let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+    for window in words.windows(SHINGLE_WORDS.min(words.len()).max(1)) {
+        let mut hasher = DefaultHasher::new();
+        window.hash(&mut hasher);
+        set.insert(hasher.finish());
+    }
+}
+
+/// Helper function to compute the Jaccard similarity of two sets
+#[allow(clippy::cast_precision_loss)]
+fn jaccard(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    let intersection = a.intersection(b).count();
+    let union = a.len() + b.len() - intersection;
+    if union == 0 {
+        0.0
+    } else {
+        intersection as f64 / union as f64
+    }
+}
+
+/// Helper function to check a system prompt against the configured length limit
+fn validate_system_prompt(
+    conn: &std::sync::MutexGuard<'_, rusqlite::Connection>,
+    system_prompt: &str,
+) -> Result<()> {
+    let max_chars = read_setting(conn, MAX_SYSTEM_PROMPT_CHARS_SETTING)?
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|max| *max > 0)
+        .unwrap_or(DEFAULT_MAX_SYSTEM_PROMPT_CHARS);
+
+    let chars = system_prompt.chars().count();
+    if chars > max_chars {
+        return Err(AppError::Validation(format!(
+            "System prompt is {chars} characters; the limit is {max_chars}"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Helper function to write a snapshot's nodes into a tree, inserting or overwriting by ID
+fn write_snapshot_nodes(
+    tx: &rusqlite::Transaction<'_>,
+    nodes: &[Node],
+    tree_id: &str,
+    resolve: impl Fn(&str) -> String,
+) -> Result<()> {
+    // Write nodes without parents first so the order of the snapshot doesn't matter
+    for node in nodes {
+        tx.execute(
+            "INSERT INTO nodes (id, tree_id, parent_id, user_content, assistant_content, summary, model, tokens, created_at, updated_at, failed, params, content_hash)
+             VALUES (?1, ?2, NULL, ?3, ?4, ?5, ?6, ?7, ?8, datetime('now'), ?9, ?10, ?11)
+             ON CONFLICT(id) DO UPDATE SET
+                 tree_id = excluded.tree_id,
+                 parent_id = NULL,
+                 user_content = excluded.user_content,
+                 assistant_content = excluded.assistant_content,
+                 summary = excluded.summary,
+                 model = excluded.model,
+                 tokens = excluded.tokens,
+                 updated_at = excluded.updated_at,
+                 deleted_at = NULL,
+                 delete_reason_id = NULL,
+                 failed = excluded.failed,
+                 params = excluded.params,
+                 content_hash = excluded.content_hash",
+            (
+                resolve(&node.id),
+                tree_id,
+                &node.user_content,
+                &node.assistant_content,
+                &node.summary,
+                &node.model,
+                &node.tokens,
+                &node.created_at,
+                i32::from(node.failed),
+                node.params.as_ref().map(params_to_json).transpose()?,
+                content_hash(&node.user_content, node.assistant_content.as_deref()),
+            ),
+        )?;
+    }
+    for node in nodes {
+        if let Some(ref parent_id) = node.parent_id {
+            tx.execute(
+                "UPDATE nodes SET parent_id = ?1 WHERE id = ?2",
+                (resolve(parent_id), resolve(&node.id)),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Helper function to map a row to a `TreeSnapshot`
+fn map_tree_snapshot(row: &rusqlite::Row<'_>) -> rusqlite::Result<TreeSnapshot> {
+    Ok(TreeSnapshot {
+        id: row.get(0)?,
+        tree_id: row.get(1)?,
+        label: row.get(2)?,
+        created_at: row.get(3)?,
+    })
+}
+
 /// Helper function to map a row to a Tree
 fn map_tree(row: &rusqlite::Row<'_>) -> rusqlite::Result<Tree> {
     Ok(Tree {
@@ -197,6 +756,7 @@ fn map_tree(row: &rusqlite::Row<'_>) -> rusqlite::Result<Tree> {
         created_at: row.get(4)?,
         updated_at: row.get(5)?,
         deleted_at: row.get(6)?,
+        default_model: row.get(7)?,
     })
 }
 
@@ -206,7 +766,7 @@ fn get_tree_by_id(
     id: &str,
 ) -> Result<Tree> {
     conn.query_row(
-        "SELECT id, project_id, name, system_prompt, created_at, updated_at, deleted_at FROM trees WHERE id = ?1",
+        "SELECT id, project_id, name, system_prompt, created_at, updated_at, deleted_at, default_model FROM trees WHERE id = ?1",
         [id],
         map_tree,
     )
@@ -215,3 +775,409 @@ fn get_tree_by_id(
         _ => AppError::Database(e),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::nodes::{
+        delete_node, get_node, list_nodes, permanently_delete_node, update_node,
+    };
+    use crate::commands::projects::delete_project;
+    use crate::commands::settings::set_setting;
+    use crate::commands::test_support::TestApp;
+
+    #[test]
+    fn update_tree_applies_patches() {
+        let app = TestApp::new();
+        let project = app.project("Project");
+        let tree = create_tree(
+            app.state(),
+            CreateTree {
+                project_id: Some(project.id.clone()),
+                name: "Tree".to_string(),
+                system_prompt: Some("Be brief".to_string()),
+                default_model: None,
+            },
+        )
+        .unwrap();
+
+        let input = serde_json::from_str(r#"{"system_prompt": null}"#).unwrap();
+        let updated = update_tree(app.state(), tree.id, input).unwrap();
+
+        assert_eq!(updated.system_prompt, None);
+        assert_eq!(updated.project_id, Some(project.id));
+        assert_eq!(updated.name, "Tree");
+    }
+
+    #[test]
+    fn effective_system_prompt_ignores_blank_prompts() {
+        let app = TestApp::new();
+        let prompted = app.tree(None, "Prompted");
+        let blank = app.tree(None, "Blank");
+        let bare = app.tree(None, "Bare");
+        app.execute(
+            "UPDATE trees SET system_prompt = 'Be brief' WHERE id = ?1",
+            [&prompted.id],
+        );
+        app.execute(
+            "UPDATE trees SET system_prompt = '  ' WHERE id = ?1",
+            [&blank.id],
+        );
+
+        assert_eq!(
+            get_effective_system_prompt(app.state(), prompted.id)
+                .unwrap()
+                .as_deref(),
+            Some("Be brief")
+        );
+        assert_eq!(
+            get_effective_system_prompt(app.state(), blank.id).unwrap(),
+            None
+        );
+        assert_eq!(
+            get_effective_system_prompt(app.state(), bare.id).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn staging_trees_follow_requested_sort() {
+        let app = TestApp::new();
+        let banana = app.tree(None, "banana");
+        app.tree(None, "Apple");
+        app.tree(None, "cherry");
+        app.execute(
+            "UPDATE trees SET created_at = '2020-01-0' || rowid, updated_at = '2020-01-0' || rowid",
+            [],
+        );
+        let names = |sort: Option<TreeSort>| -> Vec<String> {
+            list_staging_trees(app.state(), sort)
+                .unwrap()
+                .into_iter()
+                .map(|tree| tree.name)
+                .collect()
+        };
+
+        assert_eq!(names(None), ["cherry", "Apple", "banana"]);
+        assert_eq!(
+            names(Some(TreeSort::CreatedAsc)),
+            ["banana", "Apple", "cherry"]
+        );
+        assert_eq!(
+            names(Some(TreeSort::NameAsc)),
+            ["Apple", "banana", "cherry"]
+        );
+        assert_eq!(
+            names(Some(TreeSort::NameDesc)),
+            ["cherry", "banana", "Apple"]
+        );
+
+        app.node(&banana.id, None, "fresh activity");
+        assert_eq!(names(Some(TreeSort::Activity))[0], "banana");
+    }
+
+    #[test]
+    fn tree_sort_deserializes_from_snake_case() {
+        let sort: TreeSort = serde_json::from_str("\"name_asc\"").unwrap();
+        assert_eq!(sort, TreeSort::NameAsc);
+    }
+
+    #[test]
+    fn default_model_is_set_and_cleared() {
+        let app = TestApp::new();
+        let tree = create_tree(
+            app.state(),
+            CreateTree {
+                project_id: None,
+                name: "Tree".to_string(),
+                system_prompt: None,
+                default_model: Some("gpt".to_string()),
+            },
+        )
+        .unwrap();
+        assert_eq!(tree.default_model.as_deref(), Some("gpt"));
+
+        let input = serde_json::from_str(r#"{"default_model": null}"#).unwrap();
+        update_tree(app.state(), tree.id.clone(), input).unwrap();
+
+        assert_eq!(get_tree(app.state(), tree.id).unwrap().default_model, None);
+    }
+
+    #[test]
+    fn snapshot_restores_as_new_tree_and_in_place() {
+        let app = TestApp::new();
+        let tree = app.tree(None, "Tree");
+        let root = app.node(&tree.id, None, "root");
+        let child = app.node(&tree.id, Some(&root.id), "child");
+        let snapshot =
+            create_tree_snapshot(app.state(), tree.id.clone(), "v1".to_string()).unwrap();
+        assert_eq!(
+            list_tree_snapshots(app.state(), tree.id.clone())
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let input = serde_json::from_str(r#"{"user_content": "edited"}"#).unwrap();
+        update_node(app.state(), child.id.clone(), input).unwrap();
+        app.node(&tree.id, Some(&child.id), "added later");
+        permanently_delete_node(app.state(), root.id.clone()).unwrap();
+
+        let copy = restore_tree_snapshot(app.state(), snapshot.id.clone(), true).unwrap();
+        assert_ne!(copy.id, tree.id);
+        let copied = list_nodes(app.state(), copy.id).unwrap();
+        let copied_root = copied.iter().find(|node| node.parent_id.is_none()).unwrap();
+        let copied_child = copied.iter().find(|node| node.parent_id.is_some()).unwrap();
+        assert_eq!(copied.len(), 2);
+        assert_eq!(
+            copied_child.parent_id.as_deref(),
+            Some(copied_root.id.as_str())
+        );
+        assert_eq!(copied_child.user_content, "child");
+
+        let restored = restore_tree_snapshot(app.state(), snapshot.id, false).unwrap();
+        assert_eq!(restored.id, tree.id);
+        let nodes = list_nodes(app.state(), tree.id).unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert!(nodes.iter().any(|node| node.id == child.id
+            && node.parent_id.as_deref() == Some(root.id.as_str())
+            && node.user_content == "child"));
+    }
+
+    #[test]
+    fn snapshot_copy_goes_to_staging_when_project_is_gone() {
+        let app = TestApp::new();
+        let project = app.project("Project");
+        let tree = app.tree(Some(&project.id), "Tree");
+        app.node(&tree.id, None, "root");
+        let snapshot = create_tree_snapshot(app.state(), tree.id, "v1".to_string()).unwrap();
+
+        let copy = restore_tree_snapshot(app.state(), snapshot.id.clone(), true).unwrap();
+        assert_eq!(copy.project_id, Some(project.id.clone()));
+
+        delete_project(app.state(), project.id).unwrap();
+        let copy = restore_tree_snapshot(app.state(), snapshot.id, true).unwrap();
+        assert_eq!(copy.project_id, None);
+    }
+
+    #[test]
+    fn merge_trees_moves_roots_under_the_attach_point() {
+        let app = TestApp::new();
+        let source = app.tree(None, "Source");
+        let target = app.tree(None, "Target");
+        let source_root = app.node(&source.id, None, "source root");
+        let source_child = app.node(&source.id, Some(&source_root.id), "source child");
+        let target_root = app.node(&target.id, None, "target root");
+
+        let attach_elsewhere = Some(source_child.id.clone());
+        assert!(merge_trees(
+            app.state(),
+            source.id.clone(),
+            target.id.clone(),
+            attach_elsewhere
+        )
+        .is_err());
+        assert!(merge_trees(app.state(), source.id.clone(), source.id.clone(), None).is_err());
+
+        merge_trees(
+            app.state(),
+            source.id.clone(),
+            target.id.clone(),
+            Some(target_root.id.clone()),
+        )
+        .unwrap();
+
+        assert_eq!(list_nodes(app.state(), target.id).unwrap().len(), 3);
+        let moved_root = get_node(app.state(), source_root.id.clone()).unwrap();
+        assert_eq!(moved_root.parent_id, Some(target_root.id));
+        let moved_child = get_node(app.state(), source_child.id).unwrap();
+        assert_eq!(moved_child.parent_id, Some(source_root.id));
+        assert!(get_tree(app.state(), source.id)
+            .unwrap()
+            .deleted_at
+            .is_some());
+    }
+
+    /// Helper function to create a staging tree with a system prompt
+    fn tree_with_prompt(app: &TestApp, system_prompt: &str) -> Result<Tree> {
+        create_tree(
+            app.state(),
+            CreateTree {
+                project_id: None,
+                name: "Tree".to_string(),
+                system_prompt: Some(system_prompt.to_string()),
+                default_model: None,
+            },
+        )
+    }
+
+    #[test]
+    fn system_prompt_length_limit_counts_characters() {
+        let app = TestApp::new();
+        set_setting(
+            app.state(),
+            MAX_SYSTEM_PROMPT_CHARS_SETTING.to_string(),
+            "3".to_string(),
+        )
+        .unwrap();
+
+        let tree = tree_with_prompt(&app, "ééé").unwrap();
+        assert!(matches!(
+            tree_with_prompt(&app, "éééé"),
+            Err(AppError::Validation(_))
+        ));
+
+        let fits = serde_json::from_str(r#"{"system_prompt": "abc"}"#).unwrap();
+        update_tree(app.state(), tree.id.clone(), fits).unwrap();
+        let too_long = serde_json::from_str(r#"{"system_prompt": "abcd"}"#).unwrap();
+        assert!(matches!(
+            update_tree(app.state(), tree.id.clone(), too_long),
+            Err(AppError::Validation(_))
+        ));
+        let cleared = serde_json::from_str(r#"{"system_prompt": null}"#).unwrap();
+        update_tree(app.state(), tree.id, cleared).unwrap();
+    }
+
+    #[test]
+    fn unparseable_system_prompt_limit_is_ignored() {
+        let app = TestApp::new();
+        set_setting(
+            app.state(),
+            MAX_SYSTEM_PROMPT_CHARS_SETTING.to_string(),
+            "junk".to_string(),
+        )
+        .unwrap();
+
+        assert!(tree_with_prompt(&app, "long enough").is_ok());
+    }
+
+    #[test]
+    fn similar_trees_are_ranked_by_shared_wording() {
+        let app = TestApp::new();
+        let original = app.tree(None, "Original");
+        let close = app.tree(None, "Close");
+        let unrelated = app.tree(None, "Unrelated");
+        app.node(
+            &original.id,
+            None,
+            "the quick brown fox jumps over the lazy dog today",
+        );
+        app.node(
+            &close.id,
+            None,
+            "The quick brown fox jumps over the lazy dog yesterday",
+        );
+        app.node(
+            &unrelated.id,
+            None,
+            "completely different text about cooking pasta well",
+        );
+
+        let similar = find_similar_trees(app.state(), original.id.clone(), 0.0).unwrap();
+        assert_eq!(similar.len(), 2);
+        assert_eq!(similar[0].tree_id, close.id);
+        assert!(similar[0].score > 0.5);
+        assert!(similar[1].score < 0.1);
+
+        assert_eq!(
+            find_similar_trees(app.state(), original.id.clone(), 0.5)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(matches!(
+            find_similar_trees(app.state(), original.id, 1.5),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn file_all_staging_moves_only_active_staging_trees() {
+        let app = TestApp::new();
+        let target = app.project("Target");
+        let other = app.project("Other");
+        let staged: Vec<Tree> = (0..3)
+            .map(|i| app.tree(None, &format!("Staged {i}")))
+            .collect();
+        let filed = app.tree(Some(&other.id), "Filed");
+        let trashed = app.tree(None, "Trashed");
+        delete_tree(app.state(), trashed.id.clone()).unwrap();
+
+        assert_eq!(file_all_staging(app.state(), target.id.clone()).unwrap(), 3);
+
+        for tree in staged {
+            assert_eq!(
+                get_tree(app.state(), tree.id).unwrap().project_id.as_ref(),
+                Some(&target.id)
+            );
+        }
+        assert_eq!(
+            get_tree(app.state(), filed.id).unwrap().project_id,
+            Some(other.id.clone())
+        );
+        assert_eq!(get_tree(app.state(), trashed.id).unwrap().project_id, None);
+        assert_eq!(file_all_staging(app.state(), target.id).unwrap(), 0);
+
+        delete_project(app.state(), other.id.clone()).unwrap();
+        assert!(matches!(
+            file_all_staging(app.state(), other.id),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn extract_subtree_moves_the_branch_into_a_new_tree() {
+        let app = TestApp::new();
+        let project = app.project("Project");
+        let source = tree_with_prompt(&app, "Be brief").unwrap();
+        let root = app.node(&source.id, None, "root");
+        let branch = app.node(&source.id, Some(&root.id), "branch");
+        let middle = app.node(&source.id, Some(&branch.id), "middle");
+        let leaf = app.node(&source.id, Some(&middle.id), "leaf");
+        let sibling = app.node(&source.id, Some(&root.id), "sibling");
+        let deleted = app.node(&source.id, Some(&middle.id), "deleted");
+        delete_node(app.state(), deleted.id.clone(), None).unwrap();
+
+        let extracted = extract_subtree_to_tree(
+            app.state(),
+            branch.id.clone(),
+            "Split".to_string(),
+            Some(project.id.clone()),
+        )
+        .unwrap();
+
+        assert_eq!(extracted.name, "Split");
+        assert_eq!(extracted.project_id, Some(project.id));
+        assert_eq!(extracted.system_prompt.as_deref(), Some("Be brief"));
+        let ids = |tree_id: &str| -> Vec<String> {
+            list_nodes(app.state(), tree_id.to_string())
+                .unwrap()
+                .into_iter()
+                .map(|node| node.id)
+                .collect()
+        };
+        assert_eq!(ids(&source.id), [root.id, sibling.id.clone()]);
+        assert_eq!(ids(&extracted.id), [branch.id.clone(), middle.id, leaf.id]);
+        assert!(get_node(app.state(), branch.id)
+            .unwrap()
+            .parent_id
+            .is_none());
+        // Trashed descendants go along so restoring them cannot cross trees
+        assert_eq!(
+            get_node(app.state(), deleted.id).unwrap().tree_id,
+            extracted.id
+        );
+
+        assert!(matches!(
+            extract_subtree_to_tree(app.state(), "missing".to_string(), "New".to_string(), None),
+            Err(AppError::NotFound(_))
+        ));
+        assert!(extract_subtree_to_tree(
+            app.state(),
+            sibling.id,
+            "New".to_string(),
+            Some("missing".to_string())
+        )
+        .is_err());
+    }
+}
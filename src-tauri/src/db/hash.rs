@@ -0,0 +1,35 @@
+/// Strip trailing whitespace from each line and collapse runs of blank lines
+pub fn normalize_content(content: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in content.lines().map(str::trim_end) {
+        if line.is_empty() && lines.last().is_some_and(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Hash a node's normalized content
+pub fn content_hash(user_content: &str, assistant_content: Option<&str>) -> String {
+    stable_hash(&format!(
+        "{}\0{}",
+        normalize_content(user_content).trim(),
+        normalize_content(assistant_content.unwrap_or_default()).trim()
+    ))
+}
+
+/// Hash text as 16 hex digits (64-bit FNV-1a)
+/// Hashes are persisted and compared across databases, so this must not change between builds.
+pub fn stable_hash(text: &str) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    let hash = text.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    });
+    format!("{hash:016x}")
+}
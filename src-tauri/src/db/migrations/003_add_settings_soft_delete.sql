@@ -0,0 +1,5 @@
+-- Add deleted_at to settings
+ALTER TABLE settings ADD COLUMN deleted_at TEXT;
+
+-- Index for soft delete queries
+CREATE INDEX idx_settings_deleted_at ON settings(deleted_at);
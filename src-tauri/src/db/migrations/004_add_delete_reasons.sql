@@ -0,0 +1,16 @@
+-- Delete reasons table (presets for triaging trashed nodes)
+CREATE TABLE delete_reasons (
+    id TEXT PRIMARY KEY,
+    label TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+INSERT INTO delete_reasons (id, label) VALUES
+    ('irrelevant', 'Irrelevant'),
+    ('duplicate', 'Duplicate'),
+    ('wrong_answer', 'Wrong answer');
+
+-- Reason a node was moved to trash
+ALTER TABLE nodes ADD COLUMN delete_reason_id TEXT REFERENCES delete_reasons(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_nodes_delete_reason_id ON nodes(delete_reason_id);
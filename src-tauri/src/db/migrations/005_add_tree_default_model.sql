@@ -0,0 +1,2 @@
+-- Model applied to new nodes in a tree when none is given
+ALTER TABLE trees ADD COLUMN default_model TEXT;
@@ -0,0 +1,11 @@
+-- Tree snapshots table (named restore points holding a tree's serialized structure)
+CREATE TABLE tree_snapshots (
+    id TEXT PRIMARY KEY,
+    tree_id TEXT NOT NULL,
+    label TEXT NOT NULL,
+    data TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (tree_id) REFERENCES trees(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_tree_snapshots_tree_id ON tree_snapshots(tree_id);
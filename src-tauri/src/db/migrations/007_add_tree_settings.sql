@@ -0,0 +1,10 @@
+-- Tree settings table (key-value store scoped to a tree)
+CREATE TABLE tree_settings (
+    tree_id TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT,
+    PRIMARY KEY (tree_id, key),
+    FOREIGN KEY (tree_id) REFERENCES trees(id) ON DELETE CASCADE
+);
@@ -0,0 +1,2 @@
+-- Generation parameters (temperature, top_p, max_tokens, seed) as a JSON object
+ALTER TABLE nodes ADD COLUMN params TEXT CHECK (params IS NULL OR json_type(params) = 'object');
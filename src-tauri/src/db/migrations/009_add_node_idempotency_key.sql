@@ -0,0 +1,5 @@
+-- Client-supplied key that makes a retried create_node return the original node
+ALTER TABLE nodes ADD COLUMN idempotency_key TEXT;
+
+CREATE UNIQUE INDEX idx_nodes_idempotency_key ON nodes(idempotency_key)
+    WHERE idempotency_key IS NOT NULL;
@@ -0,0 +1,4 @@
+-- Hash of a node's normalized user and assistant content, for finding identical turns
+ALTER TABLE nodes ADD COLUMN content_hash TEXT;
+
+CREATE INDEX idx_nodes_content_hash ON nodes(content_hash);
@@ -1,17 +1,65 @@
+pub mod hash;
+
+use hash::content_hash;
 use rusqlite::{Connection, Result};
 use std::path::PathBuf;
 use std::sync::Mutex;
 
-/// Database migrations - each entry is (name, SQL)
+/// Step run right after a migration's SQL, for changes SQL alone can't make
+pub type MigrationHook = fn(&Connection) -> Result<()>;
+
+/// Database migrations - each entry is (name, SQL, optional post-migration hook)
 /// Migrations are applied in order and tracked in the _migrations table
-pub const MIGRATIONS: &[(&str, &str)] = &[
+pub const MIGRATIONS: &[(&str, &str, Option<MigrationHook>)] = &[
     (
         "001_initial_schema",
         include_str!("migrations/001_initial_schema.sql"),
+        None,
     ),
     (
         "002_add_soft_delete_fields",
         include_str!("migrations/002_add_soft_delete_fields.sql"),
+        None,
+    ),
+    (
+        "003_add_settings_soft_delete",
+        include_str!("migrations/003_add_settings_soft_delete.sql"),
+        None,
+    ),
+    (
+        "004_add_delete_reasons",
+        include_str!("migrations/004_add_delete_reasons.sql"),
+        None,
+    ),
+    (
+        "005_add_tree_default_model",
+        include_str!("migrations/005_add_tree_default_model.sql"),
+        None,
+    ),
+    (
+        "006_add_tree_snapshots",
+        include_str!("migrations/006_add_tree_snapshots.sql"),
+        None,
+    ),
+    (
+        "007_add_tree_settings",
+        include_str!("migrations/007_add_tree_settings.sql"),
+        None,
+    ),
+    (
+        "008_add_node_params",
+        include_str!("migrations/008_add_node_params.sql"),
+        None,
+    ),
+    (
+        "009_add_node_idempotency_key",
+        include_str!("migrations/009_add_node_idempotency_key.sql"),
+        None,
+    ),
+    (
+        "010_add_node_content_hash",
+        include_str!("migrations/010_add_node_content_hash.sql"),
+        Some(backfill_content_hashes),
     ),
 ];
 
@@ -61,9 +109,12 @@ impl Database {
             .collect();
 
         // Apply pending migrations
-        for (name, sql) in MIGRATIONS {
+        for (name, sql, hook) in MIGRATIONS {
             if !applied.contains(&(*name).to_string()) {
                 conn.execute_batch(sql)?;
+                if let Some(hook) = hook {
+                    hook(&conn)?;
+                }
                 conn.execute("INSERT INTO _migrations (name) VALUES (?1)", [name])?;
             }
         }
@@ -76,6 +127,34 @@ impl Database {
     }
 }
 
+/// Helper function to hash the content of nodes written before content hashes existed
+fn backfill_content_hashes(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT id, user_content, assistant_content FROM nodes WHERE content_hash IS NULL",
+    )?;
+    let unhashed = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    for (id, user_content, assistant_content) in unhashed {
+        conn.execute(
+            "UPDATE nodes SET content_hash = ?1 WHERE id = ?2",
+            (
+                content_hash(&user_content, assistant_content.as_deref()),
+                id,
+            ),
+        )?;
+    }
+
+    Ok(())
+}
+
 pub fn get_database_path() -> PathBuf {
     let proj_dirs = directories::ProjectDirs::from("com", "tangential", "Tangential")
         .expect("Failed to get project directories");
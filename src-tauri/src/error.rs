@@ -10,8 +10,13 @@ pub enum AppError {
     NotFound(String),
 
     #[error("Invalid input: {0}")]
-    #[allow(dead_code)]
     InvalidInput(String),
+
+    #[error("Validation failed: {0}")]
+    Validation(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 impl Serialize for AppError {
@@ -87,9 +87,11 @@ pub fn run() {
             commands::list_projects,
             commands::list_deleted_projects,
             commands::update_project,
+            commands::rename_project,
             commands::delete_project,
             commands::restore_project,
             commands::permanently_delete_project,
+            commands::export_project_ndjson,
             // Trees
             commands::create_tree,
             commands::get_tree,
@@ -97,27 +99,77 @@ pub fn run() {
             commands::list_staging_trees,
             commands::list_deleted_trees,
             commands::update_tree,
+            commands::file_all_staging,
+            commands::get_effective_system_prompt,
+            commands::find_similar_trees,
+            commands::merge_trees,
+            commands::extract_subtree_to_tree,
             commands::delete_tree,
             commands::restore_tree,
             commands::permanently_delete_tree,
+            commands::create_tree_snapshot,
+            commands::list_tree_snapshots,
+            commands::restore_tree_snapshot,
             // Nodes
             commands::create_node,
+            commands::create_variant,
             commands::get_node,
+            commands::find_node_by_hash,
             commands::list_nodes,
+            commands::list_nodes_for_trees,
+            commands::list_nodes_by_model,
             commands::get_root_nodes,
+            commands::get_node_context,
             commands::get_child_nodes,
             commands::get_node_path,
+            commands::get_ancestor_ids,
+            commands::render_path_text,
+            commands::get_path_reading_time,
+            commands::get_branch_map,
+            commands::get_tree_adjacency,
+            commands::get_branch_alternatives,
+            commands::get_longest_branch,
             commands::get_leaf_nodes,
+            commands::count_branches,
+            commands::list_resume_candidates,
+            commands::list_deleted_nodes,
+            commands::list_delete_reasons,
+            commands::get_token_distribution,
+            commands::get_daily_token_usage,
             commands::update_node,
+            commands::get_node_params,
+            commands::set_node_params,
+            commands::normalize_whitespace,
+            commands::mark_nodes_failed,
+            commands::move_siblings_from,
             commands::delete_node,
             commands::restore_node,
             commands::permanently_delete_node,
+            commands::purge_node_subtree,
+            // Search
+            commands::search_nodes_streaming,
+            commands::projects_with_node_match,
+            // Database
+            commands::audit_references,
+            commands::rehome_orphaned_nodes,
+            commands::get_pragmas,
+            commands::get_sync_state,
+            commands::merge_database,
+            commands::export_sql_dump,
             // Settings
             commands::get_setting,
             commands::get_setting_value,
+            commands::get_setting_value_or,
             commands::set_setting,
+            commands::set_settings,
             commands::list_settings,
+            commands::list_deleted_settings,
             commands::delete_setting,
+            commands::restore_setting,
+            commands::permanently_delete_setting,
+            commands::get_tree_setting,
+            commands::set_tree_setting,
+            commands::list_tree_settings,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// Project - a container for related conversation trees
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +20,76 @@ pub struct Tree {
     pub created_at: String,
     pub updated_at: Option<String>,
     pub deleted_at: Option<String>,
+    /// Model used for new nodes that don't specify one
+    pub default_model: Option<String>,
+}
+
+/// `TreeSort` - ordering options when listing trees
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeSort {
+    #[default]
+    CreatedDesc,
+    CreatedAsc,
+    NameAsc,
+    NameDesc,
+    /// Most recently active first, counting edits to the tree and its nodes
+    Activity,
+}
+
+impl TreeSort {
+    /// The ORDER BY clause for a query selecting from an unaliased `trees` table
+    pub fn order_by(self) -> &'static str {
+        match self {
+            TreeSort::CreatedDesc => "created_at DESC",
+            TreeSort::CreatedAsc => "created_at ASC",
+            TreeSort::NameAsc => "name COLLATE NOCASE ASC, created_at DESC",
+            TreeSort::NameDesc => "name COLLATE NOCASE DESC, created_at DESC",
+            TreeSort::Activity => {
+                "MAX(
+                    COALESCE(updated_at, created_at),
+                    COALESCE(
+                        (SELECT MAX(COALESCE(n.updated_at, n.created_at))
+                         FROM nodes n
+                         WHERE n.tree_id = trees.id AND n.deleted_at IS NULL),
+                        ''
+                    )
+                ) DESC, created_at DESC"
+            }
+        }
+    }
+}
+
+/// `ReadingTime` - estimated time to read a conversation path
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingTime {
+    pub word_count: u32,
+    pub minutes: f64,
+}
+
+/// `TreeSimilarity` - how closely another tree's content matches a given tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeSimilarity {
+    pub tree_id: String,
+    pub name: String,
+    /// Jaccard similarity of the two trees' word shingles, from 0.0 to 1.0
+    pub score: f64,
+}
+
+/// `TreeSnapshot` - a named restore point for a tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeSnapshot {
+    pub id: String,
+    pub tree_id: String,
+    pub label: String,
+    pub created_at: String,
+}
+
+/// `TreeSnapshotData` - the serialized contents of a snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeSnapshotData {
+    pub tree: Tree,
+    pub nodes: Vec<Node>,
 }
 
 /// Node - a single conversation turn (user prompt + assistant response)
@@ -37,6 +107,127 @@ pub struct Node {
     pub updated_at: Option<String>,
     pub deleted_at: Option<String>,
     pub failed: bool,
+    pub delete_reason_id: Option<String>,
+    pub params: Option<GenerationParams>,
+    pub content_hash: Option<String>,
+}
+
+/// `GenerationParams` - sampling settings used to produce a node's response
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenerationParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+}
+
+/// `NodeWithTree` - a node annotated with the name of its tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeWithTree {
+    #[serde(flatten)]
+    pub node: Node,
+    pub tree_name: String,
+}
+
+/// `PurgeReport` - outcome of permanently deleting a trashed branch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurgeReport {
+    pub purged: usize,
+    pub reparented: usize,
+}
+
+/// `SettingsChanged` - payload of the `settings:changed` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsChanged {
+    pub keys: Vec<String>,
+}
+
+/// `ProjectRenamed` - payload of the `project:renamed` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectRenamed {
+    pub id: String,
+    pub old_name: String,
+    pub name: String,
+}
+
+/// `ProjectExportLine` - a node with its tree and project context, one per NDJSON line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectExportLine {
+    pub project_id: String,
+    pub project_name: String,
+    pub tree_name: String,
+    #[serde(flatten)]
+    pub node: Node,
+}
+
+/// `NodeContext` - a node together with its parent and live children
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeContext {
+    pub node: Node,
+    pub parent: Option<Node>,
+    pub children: Vec<Node>,
+}
+
+/// `DailyUsage` - tokens and nodes created on a single day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyUsage {
+    pub date: String,
+    pub total_tokens: i64,
+    pub node_count: i64,
+}
+
+/// `Adjacency` - a tree's topology as node ids plus `(parent_index, child_index)` edges
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Adjacency {
+    pub ids: Vec<String>,
+    pub edges: Vec<(usize, usize)>,
+}
+
+/// `BranchPreview` - a sibling branch summarized for switching between alternatives
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchPreview {
+    pub sibling_node: Node,
+    /// Summary of the sibling's first child, or a snippet of its prompt
+    pub first_child_preview: Option<String>,
+    pub descendant_count: i64,
+}
+
+/// `DeleteReason` - a preset explaining why a node was moved to trash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteReason {
+    pub id: String,
+    pub label: String,
+    pub created_at: String,
+}
+
+/// `TreeSetting` - key-value configuration entry scoped to a tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeSetting {
+    pub tree_id: String,
+    pub key: String,
+    pub value: String,
+    pub created_at: String,
+    pub updated_at: Option<String>,
+}
+
+/// `TokenShare` - a node's portion of its tree's total tokens
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenShare {
+    pub node_id: String,
+    pub tokens: i32,
+    pub fraction: f64,
+}
+
+/// `SearchHit` - a node matching a search query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub node_id: String,
+    pub tree_id: String,
+    pub snippet: String,
 }
 
 /// Setting - key-value configuration entry
@@ -46,6 +237,121 @@ pub struct Setting {
     pub value: String,
     pub created_at: String,
     pub updated_at: Option<String>,
+    pub deleted_at: Option<String>,
+}
+
+/// Patch - a partial update to a nullable field
+///
+/// Omitting the field leaves it unchanged, `null` clears it, and a value sets it.
+/// Fields using this type must be marked `#[serde(default)]` so that a missing
+/// field deserializes to `Unchanged`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Patch<T> {
+    #[default]
+    Unchanged,
+    Clear,
+    Set(T),
+}
+
+impl<T> Patch<T> {
+    pub fn is_unchanged(&self) -> bool {
+        matches!(self, Patch::Unchanged)
+    }
+
+    /// Convert to the value to write, or `None` if the column should be left alone
+    pub fn into_change(self) -> Option<Option<T>> {
+        match self {
+            Patch::Unchanged => None,
+            Patch::Clear => Some(None),
+            Patch::Set(value) => Some(Some(value)),
+        }
+    }
+}
+
+impl<T: Serialize> Serialize for Patch<T> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Patch::Set(value) => serializer.serialize_some(value),
+            Patch::Unchanged | Patch::Clear => serializer.serialize_none(),
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patch<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match Option::<T>::deserialize(deserializer)? {
+            Some(value) => Patch::Set(value),
+            None => Patch::Clear,
+        })
+    }
+}
+
+/// `ReferenceAudit` - IDs of rows whose references don't resolve, grouped by entity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReferenceAudit {
+    pub nodes_with_missing_parent: Vec<String>,
+    pub nodes_with_missing_tree: Vec<String>,
+    pub trees_with_missing_project: Vec<String>,
+}
+
+/// Pragmas - connection settings reported for diagnostics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pragmas {
+    pub journal_mode: String,
+    pub foreign_keys: bool,
+    pub busy_timeout: i64,
+    pub synchronous: String,
+    pub page_size: i64,
+}
+
+/// `TableSyncState` - row count and latest modification time of one table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableSyncState {
+    pub count: i64,
+    pub last_modified: Option<String>,
+}
+
+/// `SyncState` - cheap summary of the database for detecting divergence between copies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncState {
+    pub projects: TableSyncState,
+    pub trees: TableSyncState,
+    pub nodes: TableSyncState,
+    pub settings: TableSyncState,
+    pub fingerprint: String,
+}
+
+/// `MergeStrategy` - how to resolve rows with the same ID when merging databases
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStrategy {
+    /// Keep whichever copy was modified most recently
+    LastWriterWins,
+    /// Keep both copies, giving the incoming one a new ID
+    KeepBoth,
+}
+
+/// `TableMergeCounts` - what happened to one table's incoming rows during a merge
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableMergeCounts {
+    pub inserted: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
+/// `MergeReport` - per-table outcome of merging another database into this one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeReport {
+    pub projects: TableMergeCounts,
+    pub trees: TableMergeCounts,
+    pub nodes: TableMergeCounts,
+    pub settings: TableMergeCounts,
 }
 
 /// Input types for creating/updating entities
@@ -65,13 +371,19 @@ pub struct CreateTree {
     pub project_id: Option<String>,
     pub name: String,
     pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub default_model: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateTree {
-    pub project_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Patch::is_unchanged")]
+    pub project_id: Patch<String>,
     pub name: Option<String>,
-    pub system_prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Patch::is_unchanged")]
+    pub system_prompt: Patch<String>,
+    #[serde(default, skip_serializing_if = "Patch::is_unchanged")]
+    pub default_model: Patch<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,14 +395,60 @@ pub struct CreateNode {
     pub summary: Option<String>,
     pub model: Option<String>,
     pub tokens: Option<i32>,
+    #[serde(default)]
+    pub params: Option<GenerationParams>,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateNode {
     pub user_content: Option<String>,
-    pub assistant_content: Option<String>,
-    pub summary: Option<String>,
-    pub model: Option<String>,
-    pub tokens: Option<i32>,
+    #[serde(default, skip_serializing_if = "Patch::is_unchanged")]
+    pub assistant_content: Patch<String>,
+    #[serde(default, skip_serializing_if = "Patch::is_unchanged")]
+    pub summary: Patch<String>,
+    #[serde(default, skip_serializing_if = "Patch::is_unchanged")]
+    pub model: Patch<String>,
+    #[serde(default, skip_serializing_if = "Patch::is_unchanged")]
+    pub tokens: Patch<i32>,
     pub failed: Option<bool>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patch_distinguishes_missing_null_and_value() {
+        let input: UpdateNode =
+            serde_json::from_str(r#"{"summary": null, "model": "gpt", "tokens": 12}"#).unwrap();
+
+        assert_eq!(input.assistant_content, Patch::Unchanged);
+        assert_eq!(input.summary, Patch::Clear);
+        assert_eq!(input.model, Patch::Set("gpt".to_string()));
+        assert_eq!(input.tokens, Patch::Set(12));
+    }
+
+    #[test]
+    fn patch_into_change() {
+        assert_eq!(Patch::<i32>::Unchanged.into_change(), None);
+        assert_eq!(Patch::<i32>::Clear.into_change(), Some(None));
+        assert_eq!(Patch::Set(3).into_change(), Some(Some(3)));
+    }
+
+    #[test]
+    fn patch_skips_unchanged_fields_when_serialized() {
+        let input = UpdateTree {
+            project_id: Patch::Unchanged,
+            name: None,
+            system_prompt: Patch::Clear,
+            default_model: Patch::Unchanged,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&input).unwrap(),
+            r#"{"name":null,"system_prompt":null}"#
+        );
+    }
+}
@@ -28,6 +28,9 @@ const MOCK_NODES: ConversationNode[] = [
     created_at: new Date().toISOString(),
     updated_at: null,
     deleted_at: null,
+    delete_reason_id: null,
+    params: null,
+    content_hash: null,
   },
   {
     id: "2",
@@ -42,6 +45,9 @@ const MOCK_NODES: ConversationNode[] = [
     created_at: new Date().toISOString(),
     updated_at: null,
     deleted_at: null,
+    delete_reason_id: null,
+    params: null,
+    content_hash: null,
   },
   {
     id: "3",
@@ -56,6 +62,9 @@ const MOCK_NODES: ConversationNode[] = [
     created_at: new Date().toISOString(),
     updated_at: null,
     deleted_at: null,
+    delete_reason_id: null,
+    params: null,
+    content_hash: null,
   },
   {
     id: "4",
@@ -70,6 +79,9 @@ const MOCK_NODES: ConversationNode[] = [
     created_at: new Date().toISOString(),
     updated_at: null,
     deleted_at: null,
+    delete_reason_id: null,
+    params: null,
+    content_hash: null,
   },
   {
     id: "5",
@@ -84,6 +96,9 @@ const MOCK_NODES: ConversationNode[] = [
     created_at: new Date().toISOString(),
     updated_at: null,
     deleted_at: null,
+    delete_reason_id: null,
+    params: null,
+    content_hash: null,
   },
 ];
 
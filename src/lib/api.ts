@@ -1,7 +1,7 @@
 // Typed wrappers around Tauri IPC commands
 // These functions provide type-safe access to the Rust backend
 
-import { invoke } from "@tauri-apps/api/core";
+import { Channel, invoke } from "@tauri-apps/api/core";
 import type {
   Project,
   Tree,
@@ -13,6 +13,27 @@ import type {
   UpdateTree,
   CreateNode,
   UpdateNode,
+  BranchMap,
+  TokenShare,
+  DeleteReason,
+  SearchHit,
+  ReferenceAudit,
+  Pragmas,
+  TreeSort,
+  NodeWithTree,
+  TreeSnapshot,
+  TreeSetting,
+  BranchPreview,
+  ReadingTime,
+  TreeSimilarity,
+  GenerationParams,
+  PurgeReport,
+  NodeContext,
+  DailyUsage,
+  Adjacency,
+  SyncState,
+  MergeStrategy,
+  MergeReport,
 } from "./types";
 
 // ============================================================================
@@ -31,11 +52,16 @@ export const projects = {
   update: (id: string, input: UpdateProject): Promise<Project> =>
     invoke("update_project", { id, input }),
 
+  rename: (id: string, name: string): Promise<Project> => invoke("rename_project", { id, name }),
+
   delete: (id: string): Promise<Project> => invoke("delete_project", { id }),
 
   restore: (id: string): Promise<Project> => invoke("restore_project", { id }),
 
   permanentlyDelete: (id: string): Promise<void> => invoke("permanently_delete_project", { id }),
+
+  exportNdjson: (projectId: string, path: string): Promise<number> =>
+    invoke("export_project_ndjson", { projectId, path }),
 };
 
 // ============================================================================
@@ -47,19 +73,44 @@ export const trees = {
 
   get: (id: string): Promise<Tree> => invoke("get_tree", { id }),
 
-  list: (projectId?: string): Promise<Tree[]> => invoke("list_trees", { projectId }),
+  list: (projectId?: string, sort?: TreeSort): Promise<Tree[]> =>
+    invoke("list_trees", { projectId, sort }),
 
-  listStaging: (): Promise<Tree[]> => invoke("list_staging_trees"),
+  listStaging: (sort?: TreeSort): Promise<Tree[]> => invoke("list_staging_trees", { sort }),
 
   listDeleted: (): Promise<Tree[]> => invoke("list_deleted_trees"),
 
   update: (id: string, input: UpdateTree): Promise<Tree> => invoke("update_tree", { id, input }),
 
+  fileAllStaging: (projectId: string): Promise<number> =>
+    invoke("file_all_staging", { projectId }),
+
+  getEffectiveSystemPrompt: (treeId: string): Promise<string | null> =>
+    invoke("get_effective_system_prompt", { treeId }),
+
+  findSimilar: (treeId: string, threshold: number): Promise<TreeSimilarity[]> =>
+    invoke("find_similar_trees", { treeId, threshold }),
+
+  merge: (sourceId: string, targetId: string, attachUnder?: string): Promise<Tree> =>
+    invoke("merge_trees", { sourceId, targetId, attachUnder }),
+
+  extractSubtree: (rootNodeId: string, newTreeName: string, projectId?: string): Promise<Tree> =>
+    invoke("extract_subtree_to_tree", { rootNodeId, newTreeName, projectId }),
+
   delete: (id: string): Promise<Tree> => invoke("delete_tree", { id }),
 
   restore: (id: string): Promise<Tree> => invoke("restore_tree", { id }),
 
   permanentlyDelete: (id: string): Promise<void> => invoke("permanently_delete_tree", { id }),
+
+  createSnapshot: (treeId: string, label: string): Promise<TreeSnapshot> =>
+    invoke("create_tree_snapshot", { treeId, label }),
+
+  listSnapshots: (treeId: string): Promise<TreeSnapshot[]> =>
+    invoke("list_tree_snapshots", { treeId }),
+
+  restoreSnapshot: (snapshotId: string, asNewTree: boolean): Promise<Tree> =>
+    invoke("restore_tree_snapshot", { snapshotId, asNewTree }),
 };
 
 // ============================================================================
@@ -69,25 +120,127 @@ export const trees = {
 export const nodes = {
   create: (input: CreateNode): Promise<Node> => invoke("create_node", { input }),
 
+  createVariant: (nodeId: string): Promise<Node> => invoke("create_variant", { nodeId }),
+
   get: (id: string): Promise<Node> => invoke("get_node", { id }),
 
+  findByHash: (hash: string): Promise<Node | null> => invoke("find_node_by_hash", { hash }),
+
   list: (treeId: string): Promise<Node[]> => invoke("list_nodes", { treeId }),
 
+  listForTrees: (treeIds: string[]): Promise<Node[]> =>
+    invoke("list_nodes_for_trees", { treeIds }),
+
+  listByModel: (model: string, limit: number): Promise<NodeWithTree[]> =>
+    invoke("list_nodes_by_model", { model, limit }),
+
   getRoots: (treeId: string): Promise<Node[]> => invoke("get_root_nodes", { treeId }),
 
+  getContext: (id: string): Promise<NodeContext> => invoke("get_node_context", { id }),
+
   getChildren: (parentId: string): Promise<Node[]> => invoke("get_child_nodes", { parentId }),
 
   getPath: (nodeId: string): Promise<Node[]> => invoke("get_node_path", { nodeId }),
 
+  getAncestorIds: (nodeId: string): Promise<string[]> => invoke("get_ancestor_ids", { nodeId }),
+
+  renderPathText: (leafNodeId: string, separator?: string): Promise<string> =>
+    invoke("render_path_text", { leafNodeId, separator }),
+
+  getPathReadingTime: (leafNodeId: string, wpm?: number): Promise<ReadingTime> =>
+    invoke("get_path_reading_time", { leafNodeId, wpm }),
+
+  getLongestBranch: (treeId: string): Promise<Node[]> =>
+    invoke("get_longest_branch", { treeId }),
+
+  getBranchAlternatives: (nodeId: string): Promise<BranchPreview[]> =>
+    invoke("get_branch_alternatives", { nodeId }),
+
+  getBranchMap: (treeId: string): Promise<BranchMap> => invoke("get_branch_map", { treeId }),
+
+  getAdjacency: (treeId: string): Promise<Adjacency> => invoke("get_tree_adjacency", { treeId }),
+
   getLeaves: (treeId: string): Promise<Node[]> => invoke("get_leaf_nodes", { treeId }),
 
+  countBranches: (treeId: string): Promise<number> => invoke("count_branches", { treeId }),
+
+  listResumeCandidates: (treeId: string): Promise<Node[]> =>
+    invoke("list_resume_candidates", { treeId }),
+
+  getTokenDistribution: (treeId: string): Promise<TokenShare[]> =>
+    invoke("get_token_distribution", { treeId }),
+
+  getDailyTokenUsage: (projectId: string | null, days: number): Promise<DailyUsage[]> =>
+    invoke("get_daily_token_usage", { projectId, days }),
+
   update: (id: string, input: UpdateNode): Promise<Node> => invoke("update_node", { id, input }),
 
-  delete: (id: string): Promise<Node> => invoke("delete_node", { id }),
+  getParams: (id: string): Promise<GenerationParams | null> => invoke("get_node_params", { id }),
+
+  setParams: (id: string, params: GenerationParams | null): Promise<Node> =>
+    invoke("set_node_params", { id, params }),
+
+  normalizeWhitespace: (treeId: string): Promise<number> =>
+    invoke("normalize_whitespace", { treeId }),
+
+  markFailed: (ids: string[]): Promise<number> => invoke("mark_nodes_failed", { ids }),
+
+  moveSiblingsFrom: (nodeId: string, newParentId: string | null): Promise<Node[]> =>
+    invoke("move_siblings_from", { nodeId, newParentId }),
+
+  listDeleted: (reasonId?: string): Promise<Node[]> => invoke("list_deleted_nodes", { reasonId }),
+
+  listDeleteReasons: (): Promise<DeleteReason[]> => invoke("list_delete_reasons"),
+
+  delete: (id: string, reasonId?: string): Promise<Node> =>
+    invoke("delete_node", { id, reasonId }),
 
   restore: (id: string): Promise<Node> => invoke("restore_node", { id }),
 
   permanentlyDelete: (id: string): Promise<void> => invoke("permanently_delete_node", { id }),
+
+  purgeSubtree: (nodeId: string): Promise<PurgeReport> => invoke("purge_node_subtree", { nodeId }),
+};
+
+// ============================================================================
+// Search
+// ============================================================================
+
+export const search = {
+  // Resolves with the number of hits sent once the search finishes
+  nodesStreaming: (
+    query: string,
+    onHit: (hit: SearchHit) => void,
+    limit?: number
+  ): Promise<number> => {
+    const channel = new Channel<SearchHit>();
+    channel.onmessage = onHit;
+    return invoke("search_nodes_streaming", { query, limit, channel });
+  },
+
+  projectsWithNodeMatch: (query: string): Promise<Project[]> =>
+    invoke("projects_with_node_match", { query }),
+};
+
+// ============================================================================
+// Database
+// ============================================================================
+
+export const database = {
+  auditReferences: (): Promise<ReferenceAudit> => invoke("audit_references"),
+
+  rehomeOrphanedNodes: (intoTreeId: string): Promise<number> =>
+    invoke("rehome_orphaned_nodes", { intoTreeId }),
+
+  getPragmas: (): Promise<Pragmas> => invoke("get_pragmas"),
+
+  getSyncState: (): Promise<SyncState> => invoke("get_sync_state"),
+
+  merge: (otherPath: string, strategy: MergeStrategy): Promise<MergeReport> =>
+    invoke("merge_database", { otherPath, strategy }),
+
+  exportSqlDump: (path: string, tables?: string[]): Promise<number> =>
+    invoke("export_sql_dump", { path, tables }),
 };
 
 // ============================================================================
@@ -99,9 +252,29 @@ export const settings = {
 
   getValue: (key: string): Promise<string | null> => invoke("get_setting_value", { key }),
 
+  getValueOr: (key: string, defaultValue: string): Promise<string> =>
+    invoke("get_setting_value_or", { key, default: defaultValue }),
+
   set: (key: string, value: string): Promise<Setting> => invoke("set_setting", { key, value }),
 
+  setMany: (entries: [string, string][]): Promise<number> => invoke("set_settings", { entries }),
+
   list: (): Promise<Setting[]> => invoke("list_settings"),
 
-  delete: (key: string): Promise<void> => invoke("delete_setting", { key }),
+  listDeleted: (): Promise<Setting[]> => invoke("list_deleted_settings"),
+
+  delete: (key: string): Promise<Setting> => invoke("delete_setting", { key }),
+
+  restore: (key: string): Promise<Setting> => invoke("restore_setting", { key }),
+
+  permanentlyDelete: (key: string): Promise<void> => invoke("permanently_delete_setting", { key }),
+
+  getForTree: (treeId: string, key: string): Promise<TreeSetting> =>
+    invoke("get_tree_setting", { treeId, key }),
+
+  setForTree: (treeId: string, key: string, value: string): Promise<TreeSetting> =>
+    invoke("set_tree_setting", { treeId, key, value }),
+
+  listForTree: (treeId: string): Promise<TreeSetting[]> =>
+    invoke("list_tree_settings", { treeId }),
 };
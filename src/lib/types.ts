@@ -8,6 +8,12 @@ export interface Project {
   deleted_at: string | null;
 }
 
+export interface ProjectRenamed {
+  id: string;
+  old_name: string;
+  name: string;
+}
+
 export interface Tree {
   id: string;
   project_id: string | null;
@@ -16,8 +22,30 @@ export interface Tree {
   created_at: string;
   updated_at: string | null;
   deleted_at: string | null;
+  default_model: string | null;
 }
 
+export interface ReadingTime {
+  word_count: number;
+  minutes: number;
+}
+
+export interface TreeSimilarity {
+  tree_id: string;
+  name: string;
+  // Jaccard similarity from 0 to 1
+  score: number;
+}
+
+export interface TreeSnapshot {
+  id: string;
+  tree_id: string;
+  label: string;
+  created_at: string;
+}
+
+export type TreeSort = "created_desc" | "created_asc" | "name_asc" | "name_desc" | "activity";
+
 export interface Node {
   id: string;
   tree_id: string;
@@ -31,6 +59,82 @@ export interface Node {
   updated_at: string | null;
   deleted_at: string | null;
   failed: boolean;
+  delete_reason_id: string | null;
+  params: GenerationParams | null;
+  content_hash: string | null;
+}
+
+export interface GenerationParams {
+  temperature?: number;
+  top_p?: number;
+  max_tokens?: number;
+  seed?: number;
+}
+
+export interface NodeContext {
+  node: Node;
+  parent: Node | null;
+  children: Node[];
+}
+
+export interface NodeWithTree extends Node {
+  tree_name: string;
+}
+
+export interface BranchPreview {
+  sibling_node: Node;
+  // Summary of the sibling's first child, or a snippet of its prompt
+  first_child_preview: string | null;
+  descendant_count: number;
+}
+
+export interface PurgeReport {
+  purged: number;
+  reparented: number;
+}
+
+export interface DeleteReason {
+  id: string;
+  label: string;
+  created_at: string;
+}
+
+// Parent node ID -> ordered child node IDs; root nodes are listed under "root"
+export type BranchMap = Record<string, string[]>;
+
+export interface Adjacency {
+  ids: string[];
+  edges: [number, number][];
+}
+
+export interface TreeSetting {
+  tree_id: string;
+  key: string;
+  value: string;
+  created_at: string;
+  updated_at: string | null;
+}
+
+export interface DailyUsage {
+  date: string;
+  total_tokens: number;
+  node_count: number;
+}
+
+export interface TokenShare {
+  node_id: string;
+  tokens: number;
+  fraction: number;
+}
+
+export interface SearchHit {
+  node_id: string;
+  tree_id: string;
+  snippet: string;
+}
+
+export interface SettingsChanged {
+  keys: string[];
 }
 
 export interface Setting {
@@ -38,9 +142,53 @@ export interface Setting {
   value: string;
   created_at: string;
   updated_at: string | null;
+  deleted_at: string | null;
+}
+
+export interface ReferenceAudit {
+  nodes_with_missing_parent: string[];
+  nodes_with_missing_tree: string[];
+  trees_with_missing_project: string[];
+}
+
+export interface TableSyncState {
+  count: number;
+  last_modified: string | null;
+}
+
+export interface SyncState {
+  projects: TableSyncState;
+  trees: TableSyncState;
+  nodes: TableSyncState;
+  settings: TableSyncState;
+  fingerprint: string;
+}
+
+export type MergeStrategy = "last_writer_wins" | "keep_both";
+
+export interface TableMergeCounts {
+  inserted: number;
+  updated: number;
+  skipped: number;
+}
+
+export interface MergeReport {
+  projects: TableMergeCounts;
+  trees: TableMergeCounts;
+  nodes: TableMergeCounts;
+  settings: TableMergeCounts;
+}
+
+export interface Pragmas {
+  journal_mode: string;
+  foreign_keys: boolean;
+  busy_timeout: number;
+  synchronous: "off" | "normal" | "full" | "extra" | "unknown";
+  page_size: number;
 }
 
 // Input types for creating/updating entities
+// For update inputs, omitting a field leaves it unchanged and `null` clears it
 
 export interface CreateProject {
   name: string;
@@ -54,12 +202,14 @@ export interface CreateTree {
   project_id?: string | null;
   name: string;
   system_prompt?: string | null;
+  default_model?: string | null;
 }
 
 export interface UpdateTree {
   project_id?: string | null;
   name?: string;
   system_prompt?: string | null;
+  default_model?: string | null;
 }
 
 export interface CreateNode {
@@ -70,13 +220,15 @@ export interface CreateNode {
   summary?: string | null;
   model?: string | null;
   tokens?: number | null;
+  params?: GenerationParams | null;
+  idempotency_key?: string | null;
 }
 
 export interface UpdateNode {
   user_content?: string;
-  assistant_content?: string;
-  summary?: string;
-  model?: string;
-  tokens?: number;
+  assistant_content?: string | null;
+  summary?: string | null;
+  model?: string | null;
+  tokens?: number | null;
   failed?: boolean;
 }